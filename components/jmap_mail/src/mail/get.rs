@@ -1,6 +1,7 @@
 use std::{
     collections::{btree_map, hash_map::Entry, BTreeMap, HashMap},
     iter::FromIterator,
+    ops::Range,
 };
 
 use jmap::{
@@ -44,6 +45,7 @@ pub struct MailGetArguments {
     pub fetch_html_body_values: bool,
     pub fetch_all_body_values: bool,
     pub max_body_value_bytes: usize,
+    pub render_html_as_text: bool,
 }
 
 impl MailGetArguments {
@@ -53,6 +55,7 @@ impl MailGetArguments {
         let mut fetch_html_body_values = false;
         let mut fetch_all_body_values = false;
         let mut max_body_value_bytes = 0;
+        let mut render_html_as_text = false;
 
         for (arg_name, arg_value) in arguments {
             match arg_name.as_str() {
@@ -60,6 +63,7 @@ impl MailGetArguments {
                 "fetchTextBodyValues" => fetch_text_body_values = arg_value.parse_bool()?,
                 "fetchHtmlBodyValues" => fetch_html_body_values = arg_value.parse_bool()?,
                 "fetchAllBodyValues" => fetch_all_body_values = arg_value.parse_bool()?,
+                "renderHtmlAsText" => render_html_as_text = arg_value.parse_bool()?,
                 "maxBodyValueBytes" => {
                     max_body_value_bytes = arg_value.parse_unsigned_int(false)?.unwrap() as usize
                 }
@@ -91,6 +95,7 @@ impl MailGetArguments {
             fetch_html_body_values,
             fetch_all_body_values,
             max_body_value_bytes,
+            render_html_as_text,
         })
     }
 }
@@ -167,10 +172,31 @@ where
 
         enum FetchRaw {
             Header,
+            // Fetch only the byte ranges of the named headers, analogous to an
+            // IMAP `BODY[HEADER.FIELDS (...)]` fetch.
+            PartialHeaders(Vec<HeaderName>),
             All,
             None,
         }
 
+        // Header names that must be fetched from the raw message because they are
+        // requested in raw form or are non-RFC (`Other`) headers.
+        let raw_header_names: Vec<HeaderName> = properties
+            .iter()
+            .filter_map(|prop| match prop {
+                MailProperties::Header(MailHeaderProperty {
+                    form: MailHeaderForm::Raw,
+                    header,
+                    ..
+                })
+                | MailProperties::Header(MailHeaderProperty {
+                    header: header @ HeaderName::Other(_),
+                    ..
+                }) => Some(header.clone()),
+                _ => None,
+            })
+            .collect();
+
         let fetch_raw = if arguments.body_properties.iter().any(|prop| {
             matches!(
                 prop,
@@ -178,19 +204,15 @@ where
             )
         }) {
             FetchRaw::All
-        } else if properties.iter().any(|prop| {
-            matches!(
-                prop,
-                MailProperties::Header(MailHeaderProperty {
-                    form: MailHeaderForm::Raw,
-                    ..
-                }) | MailProperties::Header(MailHeaderProperty {
-                    header: HeaderName::Other(_),
-                    ..
-                }) | MailProperties::BodyStructure
-            )
-        }) {
+        } else if properties
+            .iter()
+            .any(|prop| matches!(prop, MailProperties::BodyStructure))
+        {
+            // bodyStructure needs the full header region to assemble every node.
             FetchRaw::Header
+        } else if !raw_header_names.is_empty() {
+            // Only specific headers were requested; fetch just their ranges.
+            FetchRaw::PartialHeaders(raw_header_names)
         } else {
             FetchRaw::None
         };
@@ -289,6 +311,23 @@ where
                         Some(message_outline),
                     )
                 }
+                FetchRaw::PartialHeaders(headers) => {
+                    let mut message_outline = MessageOutline::deserialize(
+                        &message_data_bytes[read_bytes + message_data_len..],
+                    )
+                    .ok_or(StoreError::DataCorruption)?;
+                    // Fetch only the byte ranges of the requested headers and
+                    // remap their offsets into the compact buffer so the existing
+                    // add_raw_header path works unchanged.
+                    let raw = fetch_header_fields(
+                        self,
+                        request.account_id,
+                        document_id,
+                        message_outline.headers.get_mut(0),
+                        headers,
+                    )?;
+                    (Some(raw), Some(message_outline))
+                }
                 FetchRaw::None => (None, None),
             };
 
@@ -490,68 +529,44 @@ where
 
                         MailProperties::Preview => {
                             if !message_data.text_body.is_empty() {
-                                JSONValue::String(
-                                    preview_text(
-                                        String::from_utf8(
-                                            self.get_blob_range(
-                                                request.account_id,
-                                                Collection::Mail,
-                                                document_id,
-                                                MESSAGE_PARTS
-                                                    + message_data
-                                                        .text_body
-                                                        .get(0)
-                                                        .and_then(|p| {
-                                                            message_data.mime_parts.get(p + 1)
-                                                        })
-                                                        .ok_or(StoreError::DataCorruption)?
-                                                        .blob_index,
-                                                0..260,
-                                            )?
-                                            .ok_or(StoreError::DataCorruption)?,
-                                        )
-                                        .map_or_else(
-                                            |err| {
-                                                String::from_utf8_lossy(err.as_bytes()).into_owned()
-                                            },
-                                            |s| s,
-                                        )
-                                        .into(),
-                                        256,
-                                    )
-                                    .to_string(),
-                                )
+                                let part = message_data
+                                    .text_body
+                                    .get(0)
+                                    .and_then(|p| message_data.mime_parts.get(p + 1))
+                                    .ok_or(StoreError::DataCorruption)?;
+                                // Decode the leading bytes by charset so previews of
+                                // legacy-encoded mail are readable.
+                                let (text, _) = decode_charset(
+                                    &self
+                                        .get_blob_range(
+                                            request.account_id,
+                                            Collection::Mail,
+                                            document_id,
+                                            MESSAGE_PARTS + part.blob_index,
+                                            0..260,
+                                        )?
+                                        .ok_or(StoreError::DataCorruption)?,
+                                    charset_of(part),
+                                );
+                                JSONValue::String(preview_text(text.into(), 256).to_string())
                             } else if !message_data.html_body.is_empty() {
-                                JSONValue::String(
-                                    preview_html(
-                                        String::from_utf8(
-                                            self.get_blob(
-                                                request.account_id,
-                                                Collection::Mail,
-                                                document_id,
-                                                MESSAGE_PARTS
-                                                    + message_data
-                                                        .html_body
-                                                        .get(0)
-                                                        .and_then(|p| {
-                                                            message_data.mime_parts.get(p + 1)
-                                                        })
-                                                        .ok_or(StoreError::DataCorruption)?
-                                                        .blob_index,
-                                            )?
-                                            .ok_or(StoreError::DataCorruption)?,
-                                        )
-                                        .map_or_else(
-                                            |err| {
-                                                String::from_utf8_lossy(err.as_bytes()).into_owned()
-                                            },
-                                            |s| s,
-                                        )
-                                        .into(),
-                                        256,
-                                    )
-                                    .to_string(),
-                                )
+                                let part = message_data
+                                    .html_body
+                                    .get(0)
+                                    .and_then(|p| message_data.mime_parts.get(p + 1))
+                                    .ok_or(StoreError::DataCorruption)?;
+                                let (html, _) = decode_charset(
+                                    &self
+                                        .get_blob(
+                                            request.account_id,
+                                            Collection::Mail,
+                                            document_id,
+                                            MESSAGE_PARTS + part.blob_index,
+                                        )?
+                                        .ok_or(StoreError::DataCorruption)?,
+                                    charset_of(part),
+                                );
+                                JSONValue::String(preview_html(html.into(), 256).to_string())
                             } else {
                                 JSONValue::Null
                             }
@@ -616,17 +631,7 @@ where
 
                                         (
                                             part_id.to_string(),
-                                            add_body_value(
-                                                mime_part,
-                                                String::from_utf8(blob_entry.1).map_or_else(
-                                                    |err| {
-                                                        String::from_utf8_lossy(err.as_bytes())
-                                                            .into_owned()
-                                                    },
-                                                    |s| s,
-                                                ),
-                                                &arguments,
-                                            ),
+                                            add_body_value(mime_part, blob_entry.1, &arguments),
                                         )
                                     }),
                                 ))
@@ -669,16 +674,340 @@ where
     }
 }
 
+pub trait JMAPMailParse {
+    fn mail_parse(&self, request: GetRequest) -> jmap::Result<JSONValue>;
+}
+
+impl<T> JMAPMailParse for JMAPStore<T>
+where
+    T: for<'x> Store<'x> + 'static,
+{
+    fn mail_parse(&self, request: GetRequest) -> jmap::Result<JSONValue> {
+        let properties = request
+            .properties
+            .parse_array_items(true)?
+            .unwrap_or_else(|| {
+                vec![
+                    MailProperties::MessageId,
+                    MailProperties::InReplyTo,
+                    MailProperties::References,
+                    MailProperties::Sender,
+                    MailProperties::From,
+                    MailProperties::To,
+                    MailProperties::Cc,
+                    MailProperties::Bcc,
+                    MailProperties::ReplyTo,
+                    MailProperties::Subject,
+                    MailProperties::SentAt,
+                    MailProperties::HasAttachment,
+                    MailProperties::Preview,
+                    MailProperties::BodyValues,
+                    MailProperties::TextBody,
+                    MailProperties::HtmlBody,
+                    MailProperties::Attachments,
+                ]
+            });
+        let arguments = MailGetArguments::parse_arguments(request.arguments)?;
+
+        // The `Email/parse` method operates on blobIds rather than stored
+        // messages, so it accepts the requested ids through the `ids` field.
+        let blob_ids: Vec<BlobId> = request
+            .ids
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|id| BlobId::from_jmap_string(&id.to_jmap_string()))
+            .collect();
+
+        let mut parsed = HashMap::with_capacity(blob_ids.len());
+        let mut not_parsable = Vec::new();
+        let mut not_found = Vec::new();
+
+        for blob_id in blob_ids {
+            let raw_message = match self.get_blob_by_id(&blob_id)? {
+                Some(bytes) => bytes,
+                None => {
+                    not_found.push(JSONValue::String(blob_id.to_jmap_string()));
+                    continue;
+                }
+            };
+
+            // Parse the blob in-memory, building the same MessageData/MessageOutline
+            // produced at import time so the existing render paths apply verbatim.
+            let (mut message_data, message_outline) =
+                match build_message_structure(&mut MessageStream::new(&raw_message)) {
+                    Some(message) => message,
+                    None => {
+                        not_parsable.push(JSONValue::String(blob_id.to_jmap_string()));
+                        continue;
+                    }
+                };
+
+            let mut result: HashMap<String, JSONValue> = HashMap::new();
+            for property in &properties {
+                if let Entry::Vacant(entry) = result.entry(property.to_string()) {
+                    let value = render_parsed_property(
+                        self,
+                        property,
+                        &mut message_data,
+                        &message_outline,
+                        &raw_message,
+                        &blob_id,
+                        &arguments,
+                    )?;
+                    if !value.is_null() {
+                        entry.insert(value);
+                    }
+                }
+            }
+            parsed.insert(blob_id.to_jmap_string(), result.into());
+        }
+
+        let mut obj = HashMap::new();
+        obj.insert("parsed".to_string(), JSONValue::Object(parsed));
+        obj.insert("notParsable".to_string(), not_parsable.into());
+        obj.insert("notFound".to_string(), not_found.into());
+        Ok(obj.into())
+    }
+}
+
+/// Renders a single property against a freshly parsed (unstored) message,
+/// reusing the same `add_rfc_header`/`add_body_parts` code paths as `mail_get`.
+fn render_parsed_property<T>(
+    store: &JMAPStore<T>,
+    property: &MailProperties,
+    message_data: &mut MessageData,
+    message_outline: &MessageOutline,
+    raw_message: &[u8],
+    base_blob_id: &BlobId,
+    arguments: &MailGetArguments,
+) -> jmap::Result<JSONValue>
+where
+    T: for<'x> Store<'x> + 'static,
+{
+    Ok(match property {
+        MailProperties::MessageId => add_rfc_header(
+            &mut message_data.properties,
+            RfcHeader::MessageId,
+            MailHeaderForm::MessageIds,
+            false,
+        )?,
+        MailProperties::InReplyTo => add_rfc_header(
+            &mut message_data.properties,
+            RfcHeader::InReplyTo,
+            MailHeaderForm::MessageIds,
+            false,
+        )?,
+        MailProperties::References => add_rfc_header(
+            &mut message_data.properties,
+            RfcHeader::References,
+            MailHeaderForm::MessageIds,
+            false,
+        )?,
+        MailProperties::Sender => add_rfc_header(
+            &mut message_data.properties,
+            RfcHeader::Sender,
+            MailHeaderForm::Addresses,
+            false,
+        )?,
+        MailProperties::From => add_rfc_header(
+            &mut message_data.properties,
+            RfcHeader::From,
+            MailHeaderForm::Addresses,
+            false,
+        )?,
+        MailProperties::To => add_rfc_header(
+            &mut message_data.properties,
+            RfcHeader::To,
+            MailHeaderForm::Addresses,
+            false,
+        )?,
+        MailProperties::Cc => add_rfc_header(
+            &mut message_data.properties,
+            RfcHeader::Cc,
+            MailHeaderForm::Addresses,
+            false,
+        )?,
+        MailProperties::Bcc => add_rfc_header(
+            &mut message_data.properties,
+            RfcHeader::Bcc,
+            MailHeaderForm::Addresses,
+            false,
+        )?,
+        MailProperties::ReplyTo => add_rfc_header(
+            &mut message_data.properties,
+            RfcHeader::ReplyTo,
+            MailHeaderForm::Addresses,
+            false,
+        )?,
+        MailProperties::Subject => add_rfc_header(
+            &mut message_data.properties,
+            RfcHeader::Subject,
+            MailHeaderForm::Text,
+            false,
+        )?,
+        MailProperties::SentAt => add_rfc_header(
+            &mut message_data.properties,
+            RfcHeader::Date,
+            MailHeaderForm::Date,
+            false,
+        )?,
+        MailProperties::Size | MailProperties::HasAttachment => {
+            message_data.properties.remove(property).unwrap_or_default()
+        }
+        MailProperties::TextBody => add_body_parts(
+            &message_data.text_body,
+            &message_data.mime_parts,
+            &arguments.body_properties,
+            Some(raw_message),
+            Some(message_outline),
+            base_blob_id,
+        ),
+        MailProperties::HtmlBody => add_body_parts(
+            &message_data.html_body,
+            &message_data.mime_parts,
+            &arguments.body_properties,
+            Some(raw_message),
+            Some(message_outline),
+            base_blob_id,
+        ),
+        MailProperties::Attachments => add_body_parts(
+            &message_data.attachments,
+            &message_data.mime_parts,
+            &arguments.body_properties,
+            Some(raw_message),
+            Some(message_outline),
+            base_blob_id,
+        ),
+        MailProperties::BodyStructure => add_body_structure(
+            message_outline,
+            &message_data.mime_parts,
+            &arguments.body_properties,
+            Some(raw_message),
+            base_blob_id,
+        )
+        .unwrap_or(JSONValue::Null),
+        MailProperties::Header(MailHeaderProperty {
+            form,
+            header: HeaderName::Rfc(header),
+            all,
+        }) => add_rfc_header(&mut message_data.properties, *header, form.clone(), *all)?,
+        MailProperties::Preview => {
+            if !message_data.text_body.is_empty() {
+                let part = message_data
+                    .text_body
+                    .get(0)
+                    .and_then(|p| message_data.mime_parts.get(p + 1))
+                    .ok_or(StoreError::DataCorruption)?;
+                let (text, _) = decode_charset(
+                    &store
+                        .get_blob_by_id(&base_blob_id.clone_with_index(part.blob_index))?
+                        .ok_or(StoreError::DataCorruption)?,
+                    charset_of(part),
+                );
+                JSONValue::String(preview_text(text.into(), 256).to_string())
+            } else if !message_data.html_body.is_empty() {
+                let part = message_data
+                    .html_body
+                    .get(0)
+                    .and_then(|p| message_data.mime_parts.get(p + 1))
+                    .ok_or(StoreError::DataCorruption)?;
+                let (html, _) = decode_charset(
+                    &store
+                        .get_blob_by_id(&base_blob_id.clone_with_index(part.blob_index))?
+                        .ok_or(StoreError::DataCorruption)?,
+                    charset_of(part),
+                );
+                JSONValue::String(preview_html(html.into(), 256).to_string())
+            } else {
+                JSONValue::Null
+            }
+        }
+        MailProperties::BodyValues => {
+            let mut fetch_parts = BTreeMap::new();
+            if arguments.fetch_all_body_values || arguments.fetch_text_body_values {
+                message_data.text_body.iter().for_each(|part| {
+                    if let Some(mime_part) = message_data.mime_parts.get(*part + 1) {
+                        if let MimePartType::Html | MimePartType::Text = mime_part.mime_type {
+                            if let btree_map::Entry::Vacant(entry) =
+                                fetch_parts.entry(mime_part.blob_index)
+                            {
+                                entry.insert((mime_part, *part));
+                            }
+                        }
+                    }
+                });
+            }
+            if arguments.fetch_all_body_values || arguments.fetch_html_body_values {
+                message_data.html_body.iter().for_each(|part| {
+                    if let Some(mime_part) = message_data.mime_parts.get(*part + 1) {
+                        if let MimePartType::Html | MimePartType::Text = mime_part.mime_type {
+                            if let btree_map::Entry::Vacant(entry) =
+                                fetch_parts.entry(mime_part.blob_index)
+                            {
+                                entry.insert((mime_part, *part));
+                            }
+                        }
+                    }
+                });
+            }
+
+            if !fetch_parts.is_empty() {
+                // Unlike `mail_get`, there is no stored document to batch-fetch
+                // blobs against, so each part's bytes are pulled individually via
+                // its derived blobId.
+                let mut body_values = HashMap::with_capacity(fetch_parts.len());
+                for (mime_part, part_id) in fetch_parts.values() {
+                    if let Some(body_bytes) =
+                        store.get_blob_by_id(&base_blob_id.clone_with_index(mime_part.blob_index))?
+                    {
+                        body_values.insert(
+                            part_id.to_string(),
+                            add_body_value(mime_part, body_bytes, arguments),
+                        );
+                    }
+                }
+                JSONValue::Object(body_values)
+            } else {
+                JSONValue::Null
+            }
+        }
+        // Properties that only exist for stored messages (Id, ThreadId, MailboxIds,
+        // Keywords, …) are not available on a parsed blob.
+        _ => JSONValue::Null,
+    })
+}
+
+/// Builds an in-memory [`MessageData`]/[`MessageOutline`] pair from a raw
+/// message, mirroring the structure produced at import time. Reuses the shared
+/// import builder so `Email/parse` and `Email/import` stay in lock-step.
+fn build_message_structure(stream: &mut MessageStream) -> Option<(MessageData, MessageOutline)> {
+    crate::mail::import::build_message_structure(stream)
+}
+
 pub fn add_body_value(
     mime_part: &MimePart,
-    body_text: String,
+    body_bytes: Vec<u8>,
     arguments: &MailGetArguments,
 ) -> JSONValue {
+    // Decode the stored bytes according to the part's declared charset, flagging
+    // `isEncodingProblem` whenever a replacement character had to be emitted.
+    let (mut body_text, is_encoding_problem) = decode_charset(&body_bytes, charset_of(mime_part));
+
+    // When requested, render HTML parts to readable plain text before truncating
+    // so clients receive a usable `value` rather than markup fragments.
+    let render_as_text =
+        arguments.render_html_as_text && matches!(mime_part.mime_type, MimePartType::Html);
+    if render_as_text {
+        body_text = html_to_text(&body_text);
+    }
+
     let mut body_value = HashMap::with_capacity(3);
     body_value.insert(
         "isEncodingProblem".into(),
-        JSONValue::Bool(mime_part.is_encoding_problem),
+        JSONValue::Bool(mime_part.is_encoding_problem || is_encoding_problem),
     );
+    // Truncation is measured in bytes but must land on a UTF-8 char boundary so
+    // the emitted `value` stays valid.
     body_value.insert(
         "isTruncated".into(),
         JSONValue::Bool(
@@ -691,7 +1020,10 @@ pub fn add_body_value(
         {
             JSONValue::String(body_text)
         } else {
-            JSONValue::String(if let MimePartType::Html = mime_part.mime_type {
+            JSONValue::String(if render_as_text {
+                // Already rendered to plain text; truncate as text.
+                truncate_text(body_text.into(), arguments.max_body_value_bytes).to_string()
+            } else if let MimePartType::Html = mime_part.mime_type {
                 truncate_html(body_text.into(), arguments.max_body_value_bytes).to_string()
             } else {
                 truncate_text(body_text.into(), arguments.max_body_value_bytes).to_string()
@@ -701,6 +1033,205 @@ pub fn add_body_value(
     body_value.into()
 }
 
+/// Fetches only the raw bytes covering the requested header fields and remaps
+/// their [`HeaderOffset`] ranges so they index into the returned compact buffer.
+/// This is the JMAP analogue of an IMAP `BODY[HEADER.FIELDS (...)]` fetch and
+/// avoids pulling the whole header block for large messages.
+fn fetch_header_fields<T>(
+    store: &JMAPStore<T>,
+    account_id: store::AccountId,
+    document_id: DocumentId,
+    headers: Option<&mut HashMap<HeaderName, Vec<HeaderOffset>>>,
+    requested: &[HeaderName],
+) -> jmap::Result<Vec<u8>>
+where
+    T: for<'x> Store<'x> + 'static,
+{
+    let headers = match headers {
+        Some(headers) => headers,
+        None => return Ok(Vec::new()),
+    };
+
+    // Collect the byte ranges the requested headers actually span, merging
+    // only ranges that are adjacent or overlapping. Headers scattered across
+    // the header block (e.g. a "List-Id" near the top and a "Received" near
+    // the bottom) would otherwise force a single bounding read that covers
+    // everything in between, defeating the point of fetching only the
+    // requested fields.
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for name in requested {
+        if let Some(offsets) = headers.get(name) {
+            ranges.extend(offsets.iter().map(|offset| offset.start..offset.end));
+        }
+    }
+    if ranges.is_empty() {
+        return Ok(Vec::new());
+    }
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    // Fetch each merged range individually and concatenate the results into a
+    // compact buffer, remembering where each source range landed so the
+    // requested headers' offsets can be rebased below.
+    let mut raw = Vec::new();
+    let mut fetched: Vec<(Range<usize>, usize)> = Vec::with_capacity(merged.len());
+    for range in merged {
+        let bytes = store
+            .get_blob_range(
+                account_id,
+                Collection::Mail,
+                document_id,
+                MESSAGE_RAW,
+                range.start as u32..range.end as u32,
+            )?
+            .ok_or(StoreError::DataCorruption)?;
+        fetched.push((range, raw.len()));
+        raw.extend(bytes);
+    }
+
+    // Rebase the requested offsets from their position in the original
+    // message to their position in the newly assembled buffer.
+    for name in requested {
+        if let Some(offsets) = headers.get_mut(name) {
+            for offset in offsets {
+                let (range, buf_start) = fetched
+                    .iter()
+                    .find(|(range, _)| range.start <= offset.start && offset.end <= range.end)
+                    .expect("requested offset must fall within a fetched range");
+                let new_start = buf_start + (offset.start - range.start);
+                offset.end = new_start + (offset.end - offset.start);
+                offset.start = new_start;
+            }
+        }
+    }
+
+    Ok(raw)
+}
+
+/// Renders an HTML body to readable plain text: `<script>`/`<style>` content is
+/// dropped, `<br>`/`<p>` and other block elements become newlines, remaining
+/// tags are stripped and character entities are decoded.
+pub(crate) fn html_to_text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut chars = html.char_indices().peekable();
+
+    while let Some((_, c)) = chars.next() {
+        if c == '<' {
+            // Read the tag name.
+            let mut tag = String::new();
+            let is_closing = matches!(chars.peek(), Some((_, '/')));
+            if is_closing {
+                chars.next();
+            }
+            while let Some(&(_, c)) = chars.peek() {
+                if c.is_ascii_alphanumeric() {
+                    tag.push(c.to_ascii_lowercase());
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+
+            // Skip to the end of the tag.
+            for (_, c) in chars.by_ref() {
+                if c == '>' {
+                    break;
+                }
+            }
+
+            match tag.as_str() {
+                "script" | "style" => {
+                    // Drop everything until the matching closing tag. The
+                    // search must start at the current cursor, not byte 0 of
+                    // the whole document — otherwise every block after the
+                    // first finds a closing tag that lies behind the cursor
+                    // and skips nothing. `find_ascii_ci` also avoids
+                    // `to_lowercase()`, whose byte length can differ from the
+                    // original for non-ASCII input and would misalign the
+                    // match position against `idx`.
+                    let close = format!("</{}>", tag);
+                    if let Some(&(current, _)) = chars.peek() {
+                        if let Some(rel_pos) = find_ascii_ci(&html[current..], &close) {
+                            let end = current + rel_pos + close.len();
+                            // Fast-forward the iterator past the closing tag.
+                            while let Some(&(idx, _)) = chars.peek() {
+                                if idx >= end {
+                                    break;
+                                }
+                                chars.next();
+                            }
+                        }
+                    }
+                }
+                "br" | "p" | "div" | "li" | "tr" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                    out.push('\n');
+                }
+                _ => {}
+            }
+        } else {
+            out.push(c);
+        }
+    }
+
+    decode_html_entities(&out).trim().to_string()
+}
+
+/// Finds the byte offset of `needle` (an ASCII-only pattern) in `haystack`
+/// under ASCII case-insensitive comparison, without allocating a lowercased
+/// copy — so the returned offset stays aligned with `haystack`'s own byte
+/// positions regardless of any non-ASCII content.
+fn find_ascii_ci(haystack: &str, needle: &str) -> Option<usize> {
+    let (hb, nb) = (haystack.as_bytes(), needle.as_bytes());
+    if nb.is_empty() || nb.len() > hb.len() {
+        return None;
+    }
+    (0..=hb.len() - nb.len()).find(|&i| hb[i..i + nb.len()].eq_ignore_ascii_case(nb))
+}
+
+fn decode_html_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+}
+
+/// Returns the declared charset label of a MIME part, if any.
+pub(crate) fn charset_of(mime_part: &MimePart) -> Option<&str> {
+    match mime_part.headers.get(&MailBodyProperties::Charset) {
+        Some(JSONValue::String(charset)) => Some(charset.as_str()),
+        _ => None,
+    }
+}
+
+/// Decodes `bytes` into UTF-8 using the declared `charset`, falling back to
+/// charset auto-detection when no usable label is present. Returns the decoded string together
+/// with a flag that is `true` when the decoder had to substitute replacement
+/// characters (i.e. the bytes could not be represented in the source charset).
+pub fn decode_charset(bytes: &[u8], charset: Option<&str>) -> (String, bool) {
+    // Honor the declared label; when it is missing or not a recognized charset,
+    // fall back to statistical auto-detection rather than assuming UTF-8.
+    let encoding = match charset.and_then(|label| encoding_rs::Encoding::for_label(label.as_bytes()))
+    {
+        Some(encoding) => encoding,
+        None => {
+            let mut detector = chardetng::EncodingDetector::new();
+            detector.feed(bytes, true);
+            detector.guess(None, true)
+        }
+    };
+    let (cow, _, had_errors) = encoding.decode(bytes);
+    (cow.into_owned(), had_errors)
+}
+
 pub fn add_body_structure(
     message_outline: &MessageOutline,
     mime_parts: &[MimePart],
@@ -919,6 +1450,20 @@ fn add_body_part(
         }
     }
 
+    // A message/rfc822 part carries its own header block; expose it in the
+    // structure even when `headers` was not explicitly requested so clients get
+    // the embedded message's envelope together with its sub-structure.
+    if headers_result.is_empty()
+        && matches!(mime_part.mime_type, MimePartType::Message)
+        && has_raw_headers
+    {
+        for (header, value) in headers_raw.unwrap() {
+            if let Entry::Vacant(entry) = headers_result.entry(header.as_str().to_string()) {
+                entry.insert(get_raw_header(value));
+            }
+        }
+    }
+
     if !headers_result.is_empty() {
         body_part.insert(
             "headers".into(),
@@ -1016,7 +1561,7 @@ pub fn transform_rfc_header(
             | RfcHeader::ListSubscribe
             | RfcHeader::ListUnsubscribe,
             MailHeaderForm::URLs,
-        ) => transform_json_stringlist(value, is_collection, as_collection),
+        ) => transform_json_stringlist(value, is_collection, as_collection)?,
         (
             RfcHeader::From
             | RfcHeader::To
@@ -1036,7 +1581,7 @@ pub fn transform_rfc_header(
             is_collection,
             matches!(form, MailHeaderForm::GroupedAddresses),
             as_collection,
-        ),
+        )?,
         _ => {
             return Err(JMAPError::InvalidArguments(
                 "Invalid header property.".to_string(),
@@ -1100,6 +1645,7 @@ pub fn add_raw_header(
                 matches!(form, MailHeaderForm::GroupedAddresses),
                 all,
             )
+            .unwrap_or(JSONValue::Null)
         }
         MailHeaderForm::MessageIds => {
             let (value, _) = header_to_jmap_id(header_values);
@@ -1116,14 +1662,44 @@ pub fn add_raw_header(
     }
 }
 
+/// Maximum nesting depth accepted by the address/string-list transforms. Header
+/// values produced by the parser never exceed a handful of levels; anything
+/// deeper is rejected rather than recursed into, so a crafted payload cannot
+/// drive unbounded work or a stack overflow in the closures below.
+const MAX_JSON_DEPTH: usize = 64;
+
+/// Pre-scans `value` with an explicit work-stack (never recursing), returning an
+/// error if the array/object nesting exceeds [`MAX_JSON_DEPTH`].
+fn check_json_depth(value: &JSONValue) -> jmap::Result<()> {
+    let mut stack = vec![(value, 1usize)];
+    while let Some((node, depth)) = stack.pop() {
+        if depth > MAX_JSON_DEPTH {
+            return Err(JMAPError::InvalidArguments(
+                "Header value nesting exceeds the maximum supported depth.".to_string(),
+            ));
+        }
+        match node {
+            JSONValue::Array(list) => {
+                stack.extend(list.iter().map(|item| (item, depth + 1)));
+            }
+            JSONValue::Object(obj) => {
+                stack.extend(obj.values().map(|item| (item, depth + 1)));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
 pub fn transform_json_emailaddress(
     value: JSONValue,
     is_grouped: bool,
     is_collection: bool,
     as_grouped: bool,
     as_collection: bool,
-) -> JSONValue {
-    if let JSONValue::Array(mut list) = value {
+) -> jmap::Result<JSONValue> {
+    check_json_depth(&value)?;
+    Ok(if let JSONValue::Array(mut list) = value {
         if ((as_grouped && is_grouped) || (!as_grouped && !is_grouped))
             && ((is_collection && as_collection) || (!is_collection && !as_collection))
         {
@@ -1197,15 +1773,16 @@ pub fn transform_json_emailaddress(
         }
     } else {
         JSONValue::Null
-    }
+    })
 }
 
 pub fn transform_json_stringlist(
     value: JSONValue,
     is_collection: bool,
     as_collection: bool,
-) -> JSONValue {
-    if let JSONValue::Array(mut list) = value {
+) -> jmap::Result<JSONValue> {
+    check_json_depth(&value)?;
+    Ok(if let JSONValue::Array(mut list) = value {
         if !as_collection {
             if !is_collection {
                 JSONValue::Array(list)
@@ -1219,7 +1796,7 @@ pub fn transform_json_stringlist(
         }
     } else {
         JSONValue::Null
-    }
+    })
 }
 
 pub fn transform_json_string(value: JSONValue, as_collection: bool) -> JSONValue {
@@ -1242,6 +1819,450 @@ pub fn transform_json_string(value: JSONValue, as_collection: bool) -> JSONValue
     }
 }
 
+/// Projects `value` down to the fields named by `selectors`, optionally removing
+/// those named by `exclusions`. Pointers are `/`-separated key paths (JSON
+/// pointer `~1`/`~0` escapes honored) that transparently fan out across arrays:
+/// `addresses/email` selects `email` from every element of every `addresses`
+/// array at any nesting level produced by [`transform_json_emailaddress`]. The
+/// original array/group nesting is preserved, so projection composes with the
+/// `is_collection`/`as_collection` transforms. An empty `selectors` set selects
+/// everything (useful when only `exclusions` are given).
+pub fn project_json(value: JSONValue, selectors: &[&str], exclusions: &[&str]) -> JSONValue {
+    let selectors: Vec<Vec<String>> = selectors.iter().map(|s| split_pointer(s)).collect();
+    let exclusions: Vec<Vec<String>> = exclusions.iter().map(|s| split_pointer(s)).collect();
+    project_node(value, &[], &selectors, &exclusions).unwrap_or(JSONValue::Null)
+}
+
+fn split_pointer(pointer: &str) -> Vec<String> {
+    pointer
+        .trim_start_matches('/')
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
+
+/// `true` when `prefix` is equal to, or a leading prefix of, `path`.
+fn is_prefix(prefix: &[String], path: &[String]) -> bool {
+    prefix.len() <= path.len() && prefix == &path[..prefix.len()]
+}
+
+fn project_node(
+    value: JSONValue,
+    path: &[String],
+    selectors: &[Vec<String>],
+    exclusions: &[Vec<String>],
+) -> Option<JSONValue> {
+    // An exclusion covering this node prunes the whole subtree.
+    if exclusions.iter().any(|e| is_prefix(e, path)) {
+        return None;
+    }
+    // This node is fully selected when no selectors are given, or one of them
+    // reaches to (or above) the current path.
+    let selected = selectors.is_empty() || selectors.iter().any(|s| is_prefix(s, path));
+
+    match value {
+        JSONValue::Object(map) => {
+            let mut out = HashMap::with_capacity(map.len());
+            for (key, child) in map {
+                let mut child_path = path.to_vec();
+                child_path.push(key.clone());
+                // Keep the key when the node is fully selected, or a selector
+                // still runs through this child.
+                if selected || selectors.iter().any(|s| is_prefix(&child_path, s)) {
+                    if let Some(child) = project_node(child, &child_path, selectors, exclusions) {
+                        out.insert(key, child);
+                    }
+                }
+            }
+            Some(JSONValue::Object(out))
+        }
+        // Array indices do not consume a pointer segment: fan out, keeping path.
+        JSONValue::Array(list) => Some(JSONValue::Array(
+            list.into_iter()
+                .filter_map(|item| project_node(item, path, selectors, exclusions))
+                .collect(),
+        )),
+        leaf => {
+            if selected {
+                Some(leaf)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Parses a raw RFC 5322 `To:`/`Cc:` address-list into the nested `JSONValue`
+/// shapes consumed by [`transform_json_emailaddress`]: each `mailbox` becomes a
+/// `{name, email}` object and each `group` a `{name, addresses:[...]}` object.
+///
+/// Handles quoted-string display names (unescaping `\"`), CFWS/comment stripping
+/// between tokens, and the obsolete empty-group form (`Name:;` → empty
+/// `addresses`). `parse` then `transform_json_emailaddress` is a complete
+/// inbound pipeline.
+pub fn parse_json_emailaddress(header: &str) -> JSONValue {
+    let header = strip_comments(header);
+    let mut result = Vec::new();
+    let mut group: Option<(String, Vec<JSONValue>)> = None;
+    let mut token = String::new();
+
+    let mut chars = header.chars().peekable();
+    let flush = |token: &mut String,
+                 result: &mut Vec<JSONValue>,
+                 group: &mut Option<(String, Vec<JSONValue>)>| {
+        let trimmed = token.trim();
+        if !trimmed.is_empty() {
+            let address = parse_mailbox(trimmed);
+            match group {
+                Some((_, addresses)) => addresses.push(address),
+                None => result.push(address),
+            }
+        }
+        token.clear();
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                // Copy the quoted string verbatim (honoring escapes).
+                token.push('"');
+                while let Some(qc) = chars.next() {
+                    token.push(qc);
+                    if qc == '\\' {
+                        if let Some(escaped) = chars.next() {
+                            token.push(escaped);
+                        }
+                    } else if qc == '"' {
+                        break;
+                    }
+                }
+            }
+            ':' => {
+                // Start of a group; the accumulated token is the group name.
+                group = Some((token.trim().to_string(), Vec::new()));
+                token.clear();
+            }
+            ';' => {
+                flush(&mut token, &mut result, &mut group);
+                if let Some((name, addresses)) = group.take() {
+                    result.push(make_group(name, addresses));
+                }
+            }
+            ',' => flush(&mut token, &mut result, &mut group),
+            _ => token.push(c),
+        }
+    }
+    flush(&mut token, &mut result, &mut group);
+    if let Some((name, addresses)) = group.take() {
+        result.push(make_group(name, addresses));
+    }
+
+    JSONValue::Array(result)
+}
+
+fn make_group(name: String, addresses: Vec<JSONValue>) -> JSONValue {
+    let mut obj = HashMap::with_capacity(2);
+    obj.insert(
+        "name".to_string(),
+        if name.is_empty() {
+            JSONValue::Null
+        } else {
+            JSONValue::String(unquote(&name))
+        },
+    );
+    obj.insert("addresses".to_string(), JSONValue::Array(addresses));
+    JSONValue::Object(obj)
+}
+
+/// Parses a single `mailbox` production: `Display Name <addr>` or a bare `addr`.
+fn parse_mailbox(input: &str) -> JSONValue {
+    let mut obj = HashMap::with_capacity(2);
+    if let (Some(start), Some(end)) = (input.find('<'), input.rfind('>')) {
+        let name = input[..start].trim();
+        let email = input[start + 1..end].trim();
+        obj.insert(
+            "name".to_string(),
+            if name.is_empty() {
+                JSONValue::Null
+            } else {
+                JSONValue::String(unquote(name))
+            },
+        );
+        obj.insert("email".to_string(), JSONValue::String(email.to_string()));
+    } else {
+        obj.insert("name".to_string(), JSONValue::Null);
+        obj.insert("email".to_string(), JSONValue::String(input.trim().to_string()));
+    }
+    JSONValue::Object(obj)
+}
+
+/// Removes a quoted-string's surrounding quotes and unescapes `\"`/`\\`.
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        let mut out = String::with_capacity(value.len() - 2);
+        let mut chars = value[1..value.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                if let Some(escaped) = chars.next() {
+                    out.push(escaped);
+                }
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    } else {
+        value.to_string()
+    }
+}
+
+/// Strips RFC 5322 CFWS comments — parenthesised runs outside of quoted strings.
+fn strip_comments(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut depth = 0;
+    let mut in_quotes = false;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if depth == 0 => {
+                in_quotes = !in_quotes;
+                out.push(c);
+            }
+            '\\' if in_quotes => {
+                out.push(c);
+                if let Some(escaped) = chars.next() {
+                    out.push(escaped);
+                }
+            }
+            '(' if !in_quotes => depth += 1,
+            ')' if !in_quotes && depth > 0 => depth -= 1,
+            _ if depth > 0 => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Renders a JMAP address JSON value (the `{name, email}` / `{name, addresses}`
+/// shapes produced by [`transform_json_emailaddress`]) back into an RFC 5322
+/// header string such as `"Display Name" <addr>`. The display name is quoted and
+/// embedded `"` escaped; a `Null` name collapses to a bare `addr`. Groups render
+/// as `"Group Name": addr1, addr2;` (or `"Group Name":;` when empty).
+pub fn serialize_json_emailaddress(value: &JSONValue) -> String {
+    match value {
+        JSONValue::Array(list) => list
+            .iter()
+            .map(serialize_json_emailaddress)
+            .collect::<Vec<_>>()
+            .join(", "),
+        JSONValue::Object(obj) if obj.contains_key("addresses") => {
+            let name = obj.get("name").and_then(|n| n.to_string());
+            let members = match obj.get("addresses") {
+                Some(JSONValue::Array(members)) => members
+                    .iter()
+                    .map(serialize_json_emailaddress)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                _ => String::new(),
+            };
+            if members.is_empty() {
+                format!("{}:;", quote_display_name(name.unwrap_or("")))
+            } else {
+                format!("{}: {};", quote_display_name(name.unwrap_or("")), members)
+            }
+        }
+        JSONValue::Object(obj) => {
+            let email = obj.get("email").and_then(|e| e.to_string()).unwrap_or("");
+            match obj.get("name").and_then(|n| n.to_string()) {
+                Some(name) => format!("{} <{}>", quote_display_name(name), email),
+                None => email.to_string(),
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+/// Renders a single JMAP string value to its wire form (the raw string).
+pub fn serialize_json_string(value: &JSONValue) -> String {
+    value.to_string().unwrap_or("").to_string()
+}
+
+/// Renders a JMAP string list to a comma-separated wire string.
+pub fn serialize_json_stringlist(value: &JSONValue) -> String {
+    match value {
+        JSONValue::Array(list) => list
+            .iter()
+            .filter_map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => serialize_json_string(other),
+    }
+}
+
+/// Quotes a display name per RFC 5322, escaping embedded quotes.
+fn quote_display_name(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Assembles a complete RFC 3501 `ENVELOPE` from a message's parsed headers in a
+/// single pass, reusing [`add_rfc_header`]/[`transform_json_emailaddress`] to
+/// obtain the structured address values and rendering them to the IMAP
+/// address-list shape `(name adl mailbox host)`. `sender`/`reply-to` default to
+/// the `from` value when absent.
+pub fn add_imap_envelope(message_headers: &mut JMAPMailHeaders) -> jmap::Result<String> {
+    let date = imap_nstring_json(&add_rfc_header(
+        message_headers,
+        RfcHeader::Date,
+        MailHeaderForm::Date,
+        false,
+    )?);
+    let subject = imap_nstring_json(&add_rfc_header(
+        message_headers,
+        RfcHeader::Subject,
+        MailHeaderForm::Text,
+        false,
+    )?);
+
+    let from = add_rfc_header(
+        message_headers,
+        RfcHeader::From,
+        MailHeaderForm::GroupedAddresses,
+        false,
+    )?;
+    let sender = {
+        let value = add_rfc_header(
+            message_headers,
+            RfcHeader::Sender,
+            MailHeaderForm::GroupedAddresses,
+            false,
+        )?;
+        if value.is_null() {
+            from.clone()
+        } else {
+            value
+        }
+    };
+    let reply_to = {
+        let value = add_rfc_header(
+            message_headers,
+            RfcHeader::ReplyTo,
+            MailHeaderForm::GroupedAddresses,
+            false,
+        )?;
+        if value.is_null() {
+            from.clone()
+        } else {
+            value
+        }
+    };
+    let to = add_rfc_header(
+        message_headers,
+        RfcHeader::To,
+        MailHeaderForm::GroupedAddresses,
+        false,
+    )?;
+    let cc = add_rfc_header(
+        message_headers,
+        RfcHeader::Cc,
+        MailHeaderForm::GroupedAddresses,
+        false,
+    )?;
+    let bcc = add_rfc_header(
+        message_headers,
+        RfcHeader::Bcc,
+        MailHeaderForm::GroupedAddresses,
+        false,
+    )?;
+
+    let in_reply_to = imap_nstring_json(&add_rfc_header(
+        message_headers,
+        RfcHeader::InReplyTo,
+        MailHeaderForm::MessageIds,
+        false,
+    )?);
+    let message_id = imap_nstring_json(&add_rfc_header(
+        message_headers,
+        RfcHeader::MessageId,
+        MailHeaderForm::MessageIds,
+        false,
+    )?);
+
+    Ok(format!(
+        "({} {} {} {} {} {} {} {} {} {})",
+        date,
+        subject,
+        render_imap_address_list(&from),
+        render_imap_address_list(&sender),
+        render_imap_address_list(&reply_to),
+        render_imap_address_list(&to),
+        render_imap_address_list(&cc),
+        render_imap_address_list(&bcc),
+        in_reply_to,
+        message_id,
+    ))
+}
+
+fn imap_nstring(value: Option<&str>) -> String {
+    value.map_or_else(|| "NIL".to_string(), |v| format!("\"{}\"", v.replace('"', "\\\"")))
+}
+
+fn imap_nstring_json(value: &JSONValue) -> String {
+    imap_nstring(value.to_string())
+}
+
+/// Renders a JMAP address array (as produced by `transform_json_emailaddress`)
+/// into the RFC 3501 address-list form, flattening groups to their members with
+/// the group-start/group-end NIL markers.
+fn render_imap_address_list(value: &JSONValue) -> String {
+    let list = match value {
+        JSONValue::Array(list) if !list.is_empty() => list,
+        _ => return "NIL".to_string(),
+    };
+
+    let mut out = String::from("(");
+    for item in list {
+        if let JSONValue::Object(obj) = item {
+            if let Some(JSONValue::Array(members)) = obj.get("addresses") {
+                // Nameless groups wrap ungrouped addresses and get no markers.
+                let name = obj.get("name").and_then(|n| n.to_string());
+                if name.is_some() {
+                    // group-start: (NIL NIL "group name" NIL)
+                    out.push_str(&format!("(NIL NIL {} NIL)", imap_nstring(name)));
+                }
+                for member in members {
+                    out.push_str(&render_imap_address(member));
+                }
+                if name.is_some() {
+                    // group-end: (NIL NIL NIL NIL)
+                    out.push_str("(NIL NIL NIL NIL)");
+                }
+            } else {
+                out.push_str(&render_imap_address(item));
+            }
+        }
+    }
+    out.push(')');
+    out
+}
+
+fn render_imap_address(value: &JSONValue) -> String {
+    if let JSONValue::Object(obj) = value {
+        let name = obj.get("name").and_then(|n| n.to_string());
+        let email = obj.get("email").and_then(|e| e.to_string()).unwrap_or("");
+        let (mailbox, host) = email.split_once('@').unwrap_or((email, ""));
+        format!(
+            "({} NIL {} {})",
+            imap_nstring(name),
+            imap_nstring(Some(mailbox).filter(|m| !m.is_empty())),
+            imap_nstring(Some(host).filter(|h| !h.is_empty())),
+        )
+    } else {
+        "(NIL NIL NIL NIL)".to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -1323,11 +2344,11 @@ mod tests {
             ),
         ] {
             assert_eq!(
-                super::transform_json_stringlist(value.clone(), is_collection, false),
+                super::transform_json_stringlist(value.clone(), is_collection, false).unwrap(),
                 expected_result
             );
             assert_eq!(
-                super::transform_json_stringlist(value, is_collection, true),
+                super::transform_json_stringlist(value, is_collection, true).unwrap(),
                 expected_result_all
             );
         }
@@ -1636,7 +2657,8 @@ mod tests {
                     is_collection,
                     false,
                     false
-                ),
+                )
+                .unwrap(),
                 expected_result_single_addr,
                 "single+address"
             );
@@ -1647,7 +2669,8 @@ mod tests {
                     is_collection,
                     false,
                     true
-                ),
+                )
+                .unwrap(),
                 expected_result_all_addr,
                 "all+address"
             );
@@ -1658,7 +2681,8 @@ mod tests {
                     is_collection,
                     true,
                     false
-                ),
+                )
+                .unwrap(),
                 expected_result_single_group,
                 "single+group"
             );
@@ -1669,10 +2693,190 @@ mod tests {
                     is_collection,
                     true,
                     true
-                ),
+                )
+                .unwrap(),
                 expected_result_all_group,
                 "all+group"
             );
         }
     }
+
+    #[test]
+    fn test_serialize_emailaddress() {
+        fn email(name: Option<&str>, addr: &str) -> JSONValue {
+            let mut obj = HashMap::new();
+            obj.insert(
+                "name".to_string(),
+                name.map_or(JSONValue::Null, |n| JSONValue::String(n.to_string())),
+            );
+            obj.insert("email".to_string(), JSONValue::String(addr.to_string()));
+            JSONValue::Object(obj)
+        }
+
+        fn group(name: &str, addresses: Vec<JSONValue>) -> JSONValue {
+            let mut obj = HashMap::new();
+            obj.insert("name".to_string(), JSONValue::String(name.to_string()));
+            obj.insert("addresses".to_string(), JSONValue::Array(addresses));
+            JSONValue::Object(obj)
+        }
+
+        assert_eq!(
+            super::serialize_json_emailaddress(&email(Some("John Doe"), "jdoe@domain.com")),
+            "\"John Doe\" <jdoe@domain.com>"
+        );
+        assert_eq!(
+            super::serialize_json_emailaddress(&email(None, "jdoe@domain.com")),
+            "jdoe@domain.com"
+        );
+        assert_eq!(
+            super::serialize_json_emailaddress(&email(Some("O\"Brien"), "o@domain.com")),
+            "\"O\\\"Brien\" <o@domain.com>"
+        );
+        assert_eq!(
+            super::serialize_json_emailaddress(&group(
+                "Team",
+                vec![email(None, "a@x.com"), email(None, "b@x.com")]
+            )),
+            "\"Team\": a@x.com, b@x.com;"
+        );
+        assert_eq!(
+            super::serialize_json_emailaddress(&group("Empty", vec![])),
+            "\"Empty\":;"
+        );
+    }
+
+    #[test]
+    fn test_parse_emailaddress() {
+        fn email(name: Option<&str>, addr: &str) -> JSONValue {
+            let mut obj = HashMap::new();
+            obj.insert(
+                "name".to_string(),
+                name.map_or(JSONValue::Null, |n| JSONValue::String(n.to_string())),
+            );
+            obj.insert("email".to_string(), JSONValue::String(addr.to_string()));
+            JSONValue::Object(obj)
+        }
+
+        fn group(name: Option<&str>, addresses: Vec<JSONValue>) -> JSONValue {
+            let mut obj = HashMap::new();
+            obj.insert(
+                "name".to_string(),
+                name.map_or(JSONValue::Null, |n| JSONValue::String(n.to_string())),
+            );
+            obj.insert("addresses".to_string(), JSONValue::Array(addresses));
+            JSONValue::Object(obj)
+        }
+
+        assert_eq!(
+            super::parse_json_emailaddress("jdoe@domain.com"),
+            JSONValue::Array(vec![email(None, "jdoe@domain.com")])
+        );
+        assert_eq!(
+            super::parse_json_emailaddress("John Doe <jdoe@domain.com>"),
+            JSONValue::Array(vec![email(Some("John Doe"), "jdoe@domain.com")])
+        );
+        assert_eq!(
+            super::parse_json_emailaddress(
+                "\"Doe, John\" <jdoe@domain.com>, jane@domain.com"
+            ),
+            JSONValue::Array(vec![
+                email(Some("Doe, John"), "jdoe@domain.com"),
+                email(None, "jane@domain.com"),
+            ])
+        );
+        assert_eq!(
+            super::parse_json_emailaddress("\"O\\\"Brien\" (comment) <o@domain.com>"),
+            JSONValue::Array(vec![email(Some("O\"Brien"), "o@domain.com")])
+        );
+        assert_eq!(
+            super::parse_json_emailaddress("Team:a@x.com, b@x.com;"),
+            JSONValue::Array(vec![group(
+                Some("Team"),
+                vec![email(None, "a@x.com"), email(None, "b@x.com")]
+            )])
+        );
+        assert_eq!(
+            super::parse_json_emailaddress("Empty:;"),
+            JSONValue::Array(vec![group(Some("Empty"), vec![])])
+        );
+    }
+
+    #[test]
+    fn test_json_depth_guard() {
+        // Build a value nested well past the limit without recursing.
+        let mut value = JSONValue::Null;
+        for _ in 0..128 {
+            value = JSONValue::Array(vec![value]);
+        }
+
+        assert!(super::transform_json_emailaddress(
+            value.clone(),
+            false,
+            false,
+            false,
+            false
+        )
+        .is_err());
+        assert!(super::transform_json_stringlist(value, false, false).is_err());
+
+        // A shallow value is accepted.
+        let shallow = JSONValue::Array(vec![JSONValue::Array(vec![JSONValue::Null])]);
+        assert!(super::transform_json_stringlist(shallow, false, false).is_ok());
+    }
+
+    #[test]
+    fn test_project_json() {
+        fn email(name: &str, addr: &str) -> JSONValue {
+            let mut obj = HashMap::new();
+            obj.insert("name".to_string(), JSONValue::String(name.to_string()));
+            obj.insert("email".to_string(), JSONValue::String(addr.to_string()));
+            JSONValue::Object(obj)
+        }
+        fn only_email(addr: &str) -> JSONValue {
+            let mut obj = HashMap::new();
+            obj.insert("email".to_string(), JSONValue::String(addr.to_string()));
+            JSONValue::Object(obj)
+        }
+
+        // Fan out `email` across a flat address array.
+        let addresses = JSONValue::Array(vec![
+            email("John Doe", "jdoe@domain.com"),
+            email("Jane Smith", "jsmith@test.com"),
+        ]);
+        assert_eq!(
+            super::project_json(addresses.clone(), &["email"], &[]),
+            JSONValue::Array(vec![
+                only_email("jdoe@domain.com"),
+                only_email("jsmith@test.com"),
+            ])
+        );
+
+        // `addresses/email` fans out through a grouped structure.
+        let mut group = HashMap::new();
+        group.insert("name".to_string(), JSONValue::String("Team".to_string()));
+        group.insert("addresses".to_string(), addresses.clone());
+        let grouped = JSONValue::Array(vec![JSONValue::Object(group)]);
+
+        let mut expected_group = HashMap::new();
+        expected_group.insert(
+            "addresses".to_string(),
+            JSONValue::Array(vec![
+                only_email("jdoe@domain.com"),
+                only_email("jsmith@test.com"),
+            ]),
+        );
+        assert_eq!(
+            super::project_json(grouped.clone(), &["addresses/email"], &[]),
+            JSONValue::Array(vec![JSONValue::Object(expected_group)])
+        );
+
+        // Exclusion drops the named field while keeping the rest.
+        assert_eq!(
+            super::project_json(addresses, &[], &["name"]),
+            JSONValue::Array(vec![
+                only_email("jdoe@domain.com"),
+                only_email("jsmith@test.com"),
+            ])
+        );
+    }
 }
\ No newline at end of file
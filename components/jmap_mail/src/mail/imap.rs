@@ -0,0 +1,204 @@
+use mail_parser::MessageStructure;
+
+use crate::mail::{MailBodyProperties, MessageOutline, MimePart, MimePartType};
+use jmap::json::JSONValue;
+
+/// Renders an RFC 3501 `BODYSTRUCTURE` for a message, reusing the same
+/// [`MessageStructure`]/[`MimePart`] traversal that `add_body_structure` walks
+/// for JMAP. Each part becomes the IMAP tuple
+/// `(type subtype (param list) id description encoding size)`, with `lines`
+/// appended for `text/*` parts. `message/rfc822` parts are rendered with the
+/// same basic tuple; their envelope, nested body structure and line count are
+/// not emitted.
+pub fn add_imap_body_structure(
+    message_outline: &MessageOutline,
+    mime_parts: &[MimePart],
+    fetch_blob: impl Fn(usize) -> Option<Vec<u8>>,
+) -> String {
+    render_structure(&message_outline.body_structure, mime_parts, &fetch_blob)
+}
+
+fn render_structure<F>(structure: &MessageStructure, mime_parts: &[MimePart], fetch_blob: &F) -> String
+where
+    F: Fn(usize) -> Option<Vec<u8>>,
+{
+    match structure {
+        MessageStructure::Part(part_id) => mime_parts
+            .get(part_id + 1)
+            .map(|part| render_part(part, fetch_blob))
+            .unwrap_or_else(|| "NIL".to_string()),
+        MessageStructure::List(parts) => {
+            // An implicit multipart container with no MimePart of its own:
+            // concatenate the children and fall back to the RFC 3501 default
+            // subtype for an unlabeled multipart.
+            let children: String = parts
+                .iter()
+                .map(|p| render_structure(p, mime_parts, fetch_blob))
+                .collect();
+            format!("({} \"MIXED\")", children)
+        }
+        MessageStructure::MultiPart((part_id, parts)) => {
+            let children: String = parts
+                .iter()
+                .map(|p| render_structure(p, mime_parts, fetch_blob))
+                .collect();
+            let subtype = mime_parts
+                .get(part_id + 1)
+                .and_then(subtype_of)
+                .unwrap_or_else(|| "MIXED".to_string());
+            format!("({} \"{}\")", children, subtype)
+        }
+    }
+}
+
+fn render_part<F>(mime_part: &MimePart, fetch_blob: &F) -> String
+where
+    F: Fn(usize) -> Option<Vec<u8>>,
+{
+    let (type_, subtype) = type_subtype(mime_part);
+    let charset = string_header(mime_part, &MailBodyProperties::Charset);
+    let id = nstring(string_header(mime_part, &MailBodyProperties::Cid));
+    let size = mime_part
+        .headers
+        .get(&MailBodyProperties::Size)
+        .and_then(|v| v.to_unsigned_int())
+        .unwrap_or(0);
+
+    let params = match charset {
+        Some(charset) => format!("(\"CHARSET\" \"{}\")", charset),
+        None => "NIL".to_string(),
+    };
+
+    // Inspect the decoded octets once to derive the transfer encoding and, for
+    // text parts, the true line count, rather than fabricating either value.
+    let body = fetch_blob(mime_part.blob_index);
+    let encoding = body.as_deref().map_or("7BIT", content_encoding);
+
+    let base = format!(
+        "(\"{}\" \"{}\" {} {} NIL \"{}\" {}",
+        type_, subtype, params, id, encoding, size
+    );
+
+    match mime_part.mime_type {
+        // text/* parts carry a trailing line count.
+        MimePartType::Text | MimePartType::Html => {
+            let lines = body.as_deref().map_or(0, line_count);
+            format!("{} {})", base, lines)
+        }
+        _ => format!("{})", base),
+    }
+}
+
+fn type_subtype(mime_part: &MimePart) -> (String, String) {
+    match mime_part.mime_type {
+        MimePartType::Text => ("TEXT".to_string(), "PLAIN".to_string()),
+        MimePartType::Html => ("TEXT".to_string(), "HTML".to_string()),
+        MimePartType::Message => ("MESSAGE".to_string(), "RFC822".to_string()),
+        _ => string_header(mime_part, &MailBodyProperties::Type)
+            .and_then(|t| t.split_once('/').map(|(a, b)| (a.to_uppercase(), b.to_uppercase())))
+            .unwrap_or_else(|| ("APPLICATION".to_string(), "OCTET-STREAM".to_string())),
+    }
+}
+
+fn subtype_of(mime_part: &MimePart) -> Option<String> {
+    string_header(mime_part, &MailBodyProperties::Type)
+        .and_then(|t| t.split_once('/').map(|(_, b)| b.to_uppercase()))
+}
+
+fn string_header<'x>(mime_part: &'x MimePart, property: &MailBodyProperties) -> Option<&'x str> {
+    match mime_part.headers.get(property) {
+        Some(JSONValue::String(value)) => Some(value.as_str()),
+        _ => None,
+    }
+}
+
+fn nstring(value: Option<&str>) -> String {
+    value.map_or_else(|| "NIL".to_string(), |v| format!("\"{}\"", v))
+}
+
+/// Counts the CRLF-delimited lines in a body, as required for the `lines` field
+/// of a `text/*` `BODYSTRUCTURE`. The count is the number of line terminators in
+/// the decoded octets.
+fn line_count(bytes: &[u8]) -> u64 {
+    bytes.iter().filter(|&&b| b == b'\n').count() as u64
+}
+
+/// Derives the IMAP content-transfer-encoding label for the literal we actually
+/// serve from a part's decoded octets: `7BIT` for pure ASCII, `BINARY` when NUL
+/// bytes are present and `8BIT` otherwise.
+fn content_encoding(bytes: &[u8]) -> &'static str {
+    if bytes.iter().any(|&b| b == 0) {
+        "BINARY"
+    } else if bytes.iter().all(|&b| b < 128) {
+        "7BIT"
+    } else {
+        "8BIT"
+    }
+}
+
+/// Resolves a dotted IMAP section path (e.g. `2.1.3`) to the `blob_index` of the
+/// addressed part and returns the raw octets, honoring an optional `<start.count>`
+/// partial range.
+///
+/// Section numbering is 1-based; a `message/rfc822` part's inner body shifts the
+/// numbering down one level. Partial ranges clamp `count` to the remaining
+/// length and return an empty slice (not an error) when `start` exceeds the part
+/// size.
+pub fn fetch_section(
+    part_path: &[usize],
+    partial: Option<(u32, u32)>,
+    structure: &MessageStructure,
+    mime_parts: &[MimePart],
+    fetch_blob: impl Fn(usize) -> Option<Vec<u8>>,
+) -> Option<Vec<u8>> {
+    let blob_index = resolve_section(part_path, structure)?;
+    let bytes = fetch_blob(mime_parts.get(blob_index)?.blob_index)?;
+
+    Some(match partial {
+        Some((start, count)) => {
+            let start = start as usize;
+            if start >= bytes.len() {
+                Vec::new()
+            } else {
+                let end = std::cmp::min(start + count as usize, bytes.len());
+                bytes[start..end].to_vec()
+            }
+        }
+        None => bytes,
+    })
+}
+
+/// Walks the structure tree following the 1-based `part_path`, returning the
+/// index into `mime_parts` of the addressed node.
+fn resolve_section(part_path: &[usize], structure: &MessageStructure) -> Option<usize> {
+    let mut current = structure;
+    let mut last_part = None;
+
+    for &section in part_path {
+        if section == 0 {
+            return None;
+        }
+        let idx = section - 1;
+        match current {
+            MessageStructure::MultiPart((_, parts)) | MessageStructure::List(parts) => {
+                let next = parts.get(idx)?;
+                match next {
+                    MessageStructure::Part(part_id) => last_part = Some(*part_id + 1),
+                    _ => {}
+                }
+                current = next;
+            }
+            MessageStructure::Part(part_id) => {
+                // A message/rfc822 leaf: its inner body shifts numbering down a
+                // level, so a path component addresses the embedded part.
+                last_part = Some(part_id + 1 + idx);
+                break;
+            }
+        }
+    }
+
+    match current {
+        MessageStructure::Part(part_id) => Some(*part_id + 1),
+        _ => last_part,
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::{BTreeMap, HashMap};
+
+use jmap::{
+    id::{JMAPIdSerialize},
+    json::JSONValue,
+    request::GetRequest,
+};
+use store::{serialize::StoreDeserialize, Collection, JMAPId, JMAPIdPrefix, Store, StoreError};
+use store::{JMAPStore};
+
+use crate::mail::get::{charset_of, decode_charset, html_to_text};
+use crate::mail::{
+    MessageData, MimePartType, MESSAGE_DATA, MESSAGE_PARTS,
+};
+use mail_parser::parsers::preview::preview_text;
+
+/// Number of bytes a snippet window spans, centered on the first match.
+const SNIPPET_SIZE: usize = 256;
+
+pub trait JMAPSearchSnippet {
+    fn search_snippet_get(&self, request: GetRequest) -> jmap::Result<JSONValue>;
+}
+
+impl<T> JMAPSearchSnippet for JMAPStore<T>
+where
+    T: for<'x> Store<'x> + 'static,
+{
+    fn search_snippet_get(&self, request: GetRequest) -> jmap::Result<JSONValue> {
+        // Extract the text search terms from the query filter.
+        let terms = request
+            .arguments
+            .get("filter")
+            .map(extract_terms)
+            .unwrap_or_default();
+
+        let mut list = Vec::new();
+        let mut not_found = Vec::new();
+
+        for jmap_id in request.ids.unwrap_or_default() {
+            let document_id = jmap_id.get_document_id();
+
+            let message_data_bytes = match self.get_blob(
+                request.account_id,
+                Collection::Mail,
+                document_id,
+                MESSAGE_DATA,
+            )? {
+                Some(bytes) => bytes,
+                None => {
+                    not_found.push(JSONValue::String(jmap_id.to_jmap_string()));
+                    continue;
+                }
+            };
+
+            let (message_data_len, read_bytes) = store::leb128::Leb128::from_leb128_bytes(
+                &message_data_bytes[..],
+            )
+            .ok_or(StoreError::DataCorruption)?;
+            let message_data = MessageData::deserialize(
+                &message_data_bytes[read_bytes..read_bytes + message_data_len],
+            )
+            .ok_or(StoreError::DataCorruption)?;
+
+            // Subject from the parsed header properties.
+            let subject = message_data
+                .properties
+                .get(&crate::mail::MailProperties::Subject)
+                .and_then(|v| v.to_string().map(|s| s.to_string()));
+
+            // Leading plain-text body via the same body-value fetch path as
+            // `Email/get`: prefer the text parts and fall back to the HTML body
+            // (converted to text) so multi-part and HTML-only messages are both
+            // covered.
+            let preview =
+                self.snippet_body_text(request.account_id, document_id, &message_data)?;
+
+            let mut entry = HashMap::with_capacity(3);
+            entry.insert(
+                "emailId".to_string(),
+                JSONValue::String(jmap_id.to_jmap_string()),
+            );
+            entry.insert(
+                "subject".to_string(),
+                subject
+                    .map(|s| JSONValue::String(highlight(&s, &terms)))
+                    .unwrap_or(JSONValue::Null),
+            );
+            entry.insert(
+                "preview".to_string(),
+                preview
+                    .map(|s| JSONValue::String(highlight(&s, &terms)))
+                    .unwrap_or(JSONValue::Null),
+            );
+            list.push(JSONValue::Object(entry));
+        }
+
+        let mut obj = HashMap::new();
+        obj.insert("list".to_string(), list.into());
+        obj.insert("notFound".to_string(), not_found.into());
+        Ok(obj.into())
+    }
+}
+
+impl<T> JMAPStore<T>
+where
+    T: for<'x> Store<'x> + 'static,
+{
+    /// Reads the leading plain text of a message for snippet extraction, reusing
+    /// the body-value selection rules of `Email/get`: the text body is preferred,
+    /// falling back to the HTML body (converted to text) for HTML-only messages,
+    /// and every selected `text/*` or `text/html` part is read in one multi-get
+    /// and concatenated so multi-part bodies are covered. Returns `None` when the
+    /// message carries no readable body.
+    fn snippet_body_text(
+        &self,
+        account_id: store::AccountId,
+        document_id: store::DocumentId,
+        message_data: &MessageData,
+    ) -> jmap::Result<Option<String>> {
+        let parts = if !message_data.text_body.is_empty() {
+            &message_data.text_body
+        } else {
+            &message_data.html_body
+        };
+
+        // Deduplicate on blob index, keeping document order, exactly as the
+        // `MailProperties::BodyValues` branch does.
+        let mut fetch = BTreeMap::new();
+        for part_id in parts {
+            if let Some(mime_part) = message_data.mime_parts.get(part_id + 1) {
+                if let MimePartType::Text | MimePartType::Html = mime_part.mime_type {
+                    fetch
+                        .entry(mime_part.blob_index + MESSAGE_PARTS)
+                        .or_insert(mime_part);
+                }
+            }
+        }
+        if fetch.is_empty() {
+            return Ok(None);
+        }
+
+        // A few snippet windows' worth of leading text is enough to locate the
+        // first match and frame a window around it.
+        let blobs = fetch
+            .keys()
+            .map(|k| (*k, 0..(SNIPPET_SIZE * 4) as u32))
+            .collect();
+
+        let mut body = String::new();
+        for (key, bytes) in self.get_blobs(account_id, Collection::Mail, document_id, blobs)? {
+            let mime_part = fetch.get(&key).unwrap();
+            let (text, _) = decode_charset(&bytes, charset_of(mime_part));
+            let text = if let MimePartType::Html = mime_part.mime_type {
+                html_to_text(&text)
+            } else {
+                text
+            };
+            let text = text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            if !body.is_empty() {
+                body.push('\n');
+            }
+            body.push_str(text);
+        }
+
+        Ok(if body.is_empty() { None } else { Some(body) })
+    }
+}
+
+/// Flattens a JMAP `filter` object into the set of lowercased search terms found
+/// in its `text`/`subject`/`body` conditions.
+fn extract_terms(filter: &JSONValue) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut stack = vec![filter];
+    while let Some(node) = stack.pop() {
+        if let JSONValue::Object(obj) = node {
+            for (key, value) in obj {
+                match key.as_str() {
+                    "text" | "subject" | "body" => {
+                        if let Some(text) = value.to_string() {
+                            terms.extend(tokenize(text));
+                        }
+                    }
+                    "conditions" => {
+                        if let JSONValue::Array(conditions) = value {
+                            stack.extend(conditions.iter());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    terms
+}
+
+/// Lowercases and splits text into whole word tokens on Unicode word boundaries.
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+/// Builds a highlighted fragment: locates the first matching term, emits a
+/// `SNIPPET_SIZE`-byte window centered on it (snapped to char boundaries) and
+/// wraps every whole-token match inside the window in `<mark>…</mark>`. Falls
+/// back to a plain leading preview when no term matches.
+fn highlight(text: &str, terms: &[String]) -> String {
+    let (mut tokens, last_start) =
+        text.char_indices()
+            .fold((Vec::new(), None::<usize>), |(mut acc, start), (idx, c)| {
+                match (start, c.is_alphanumeric()) {
+                    (None, true) => (acc, Some(idx)),
+                    (Some(s), false) => {
+                        acc.push((s, &text[s..idx]));
+                        (acc, None)
+                    }
+                    _ => (acc, start),
+                }
+            });
+    // The fold only flushes a token when it hits a non-alphanumeric char, so a
+    // token running to the end of `text` (e.g. the last word of a subject) is
+    // never pushed above — flush it here.
+    if let Some(s) = last_start {
+        tokens.push((s, &text[s..]));
+    }
+    let tokens = tokens;
+
+    let first_match = tokens
+        .iter()
+        .find(|(_, tok)| terms.iter().any(|t| t == &tok.to_lowercase()));
+
+    // Determine the window.
+    let (win_start, win_end) = if let Some((pos, _)) = first_match {
+        let half = SNIPPET_SIZE / 2;
+        // Center on the match, snap to char boundaries, then nudge to the
+        // nearest surrounding whitespace so words aren't cut mid-token.
+        let start = snap_to_whitespace(
+            text,
+            floor_char_boundary(text, pos.saturating_sub(half)),
+            false,
+        );
+        let end = snap_to_whitespace(
+            text,
+            ceil_char_boundary(text, std::cmp::min(start + SNIPPET_SIZE, text.len())),
+            true,
+        );
+        (start, end)
+    } else {
+        // No match: plain leading preview.
+        return preview_text(text.to_string().into(), SNIPPET_SIZE).to_string();
+    };
+
+    // HTML-escape the window, then insert marks around in-window matches.
+    let mut out = String::with_capacity(win_end - win_start + 16);
+    if win_start > 0 {
+        out.push('…');
+    }
+    let mut cursor = win_start;
+    for (pos, tok) in &tokens {
+        if *pos < win_start || pos + tok.len() > win_end {
+            continue;
+        }
+        if terms.iter().any(|t| t == &tok.to_lowercase()) {
+            out.push_str(&html_escape(&text[cursor..*pos]));
+            out.push_str("<mark>");
+            out.push_str(&html_escape(tok));
+            out.push_str("</mark>");
+            cursor = pos + tok.len();
+        }
+    }
+    out.push_str(&html_escape(&text[cursor..win_end]));
+    if win_end < text.len() {
+        out.push('…');
+    }
+    merge_marks(out)
+}
+
+/// Moves `index` to the nearest whitespace boundary within a small lookahead so
+/// that window edges fall between words where possible. `forward` snaps towards
+/// the end of the text, otherwise towards the start.
+fn snap_to_whitespace(text: &str, index: usize, forward: bool) -> usize {
+    const LOOKAHEAD: usize = 16;
+    let bytes = text.as_bytes();
+    if forward {
+        for i in index..std::cmp::min(index + LOOKAHEAD, text.len()) {
+            if bytes[i].is_ascii_whitespace() {
+                return i;
+            }
+        }
+    } else {
+        for i in (index.saturating_sub(LOOKAHEAD)..index).rev() {
+            if bytes[i].is_ascii_whitespace() {
+                return i + 1;
+            }
+        }
+    }
+    index
+}
+
+/// Collapses `</mark><whitespace><mark>` runs into a single highlight so that
+/// adjacent matches read as one marked span.
+fn merge_marks(text: String) -> String {
+    let mut out = text;
+    let mut search_from = 0;
+    while let Some(rel) = out[search_from..].find("</mark>") {
+        let close_start = search_from + rel;
+        let after_close = close_start + "</mark>".len();
+        let rest = &out[after_close..];
+        let gap_len = rest.len() - rest.trim_start().len();
+        if rest[gap_len..].starts_with("<mark>") {
+            // Drop the re-opened <mark> and the preceding </mark>, fusing the spans.
+            let mark_start = after_close + gap_len;
+            out.replace_range(mark_start..mark_start + "<mark>".len(), "");
+            out.replace_range(close_start..after_close, "");
+        } else {
+            search_from = after_close;
+        }
+    }
+    out
+}
+
+fn html_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while index > 0 && !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    while index < text.len() && !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
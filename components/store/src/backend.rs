@@ -0,0 +1,67 @@
+use crate::serialize::LogKey;
+use crate::{ColumnFamily, Direction, Store, StoreError, WriteOperation};
+
+/// Backend-agnostic contract for the raft log and value store.
+///
+/// The [`LogKey`] prefixes, forward/backward range scans and atomic multi-put
+/// for [`WriteOperation`] batches are lifted into a small set of methods here
+/// so that the same log could in principle run on any engine that can satisfy
+/// them (RocksDB, a SQL backend behind a connection pool, FoundationDB, …).
+///
+/// A blanket implementation is provided for every [`Store`], so existing
+/// backends get the contract for free by implementing the lower-level
+/// `iterator`/`write` primitives. Note that `store::raft` and `store::backup`
+/// still call `Store::iterator`/`Store::write` directly rather than going
+/// through this trait, so a type that implements only `KeyValueBackend` (like
+/// `store_sql::SqlStore`) cannot yet back a full `JMAPStore` — rerouting the
+/// raft/log code through `KeyValueBackend` is required before it can.
+pub trait KeyValueBackend {
+    /// Scans `cf` starting at `from` in `direction`, returning every
+    /// `(key, value)` pair whose key starts with `prefix`.
+    fn range_scan(
+        &self,
+        cf: ColumnFamily,
+        prefix: &[u8],
+        from: &[u8],
+        direction: Direction,
+    ) -> crate::Result<Vec<(Box<[u8]>, Box<[u8]>)>>;
+
+    /// Applies a batch of [`WriteOperation`]s atomically.
+    fn multi_put(&self, batch: Vec<WriteOperation>) -> crate::Result<()>;
+
+    /// Convenience scan over the raft log key-space.
+    fn raft_range_scan(
+        &self,
+        from: &[u8],
+        direction: Direction,
+    ) -> crate::Result<Vec<(Box<[u8]>, Box<[u8]>)>> {
+        self.range_scan(ColumnFamily::Logs, &[LogKey::RAFT_KEY_PREFIX], from, direction)
+    }
+}
+
+impl<T> KeyValueBackend for T
+where
+    T: for<'x> Store<'x> + 'static,
+{
+    fn range_scan(
+        &self,
+        cf: ColumnFamily,
+        prefix: &[u8],
+        from: &[u8],
+        direction: Direction,
+    ) -> crate::Result<Vec<(Box<[u8]>, Box<[u8]>)>> {
+        let mut results = Vec::new();
+        for (key, value) in self.iterator(cf, from, direction)? {
+            if !key.starts_with(prefix) {
+                break;
+            }
+            results.push((key, value));
+        }
+        Ok(results)
+    }
+
+    fn multi_put(&self, batch: Vec<WriteOperation>) -> crate::Result<()> {
+        self.write(batch)
+            .map_err(|err| StoreError::InternalError(format!("Batch write failed: {:?}", err)))
+    }
+}
@@ -73,4 +73,50 @@ impl_unsigned_leb128!(u16);
 impl_unsigned_leb128!(u32);
 impl_unsigned_leb128!(u64);
 impl_unsigned_leb128!(u128);
-impl_unsigned_leb128!(usize);
\ No newline at end of file
+impl_unsigned_leb128!(usize);
+
+// Signed LEB128 using zigzag mapping so that small-magnitude negative deltas
+// stay short: `(n << 1) ^ (n >> bits-1)` on encode, the inverse on decode.
+macro_rules! impl_signed_leb128 {
+    ($int_ty:ident, $uint_ty:ident, $bits:expr) => {
+        impl Leb128 for $int_ty {
+            #[inline]
+            fn to_leb128_bytes(&self, out: &mut Vec<u8>) {
+                let zigzag = ((*self << 1) ^ (*self >> ($bits - 1))) as $uint_ty;
+                zigzag.to_leb128_bytes(out);
+            }
+
+            #[inline]
+            fn from_leb128_bytes(slice: &[u8]) -> Option<($int_ty, usize)> {
+                let (zigzag, position) = $uint_ty::from_leb128_bytes(slice)?;
+                Some((unzigzag(zigzag), position))
+            }
+
+            #[inline]
+            fn from_leb128_it<'x, T>(it: T) -> Option<$int_ty>
+            where
+                T: Iterator<Item = &'x u8>,
+            {
+                Some(unzigzag($uint_ty::from_leb128_it(it)?))
+            }
+        }
+
+        #[inline]
+        fn unzigzag(zigzag: $uint_ty) -> $int_ty {
+            ((zigzag >> 1) as $int_ty) ^ -((zigzag & 1) as $int_ty)
+        }
+    };
+}
+
+mod signed_i32 {
+    use super::Leb128;
+    impl_signed_leb128!(i32, u32, 32);
+}
+mod signed_i64 {
+    use super::Leb128;
+    impl_signed_leb128!(i64, u64, 64);
+}
+mod signed_isize {
+    use super::Leb128;
+    impl_signed_leb128!(isize, usize, std::mem::size_of::<isize>() * 8);
+}
\ No newline at end of file
@@ -47,8 +47,56 @@ pub struct Change {
     pub collection: Collection,
 }
 
+/// Serialization version byte prepended to delta-encoded entries. Entries
+/// written before delta-encoding carry no version byte (their first byte is the
+/// leb128-encoded `account_id`), so on decode a leading [`Entry::VERSION`] byte
+/// selects the new format and anything else falls back to the legacy decoder.
 impl Entry {
+    const VERSION: u8 = 1;
+
     pub fn deserialize(value: &[u8], raft_id: RaftId) -> Option<Self> {
+        if value.first().copied() == Some(Entry::VERSION) {
+            Entry::deserialize_v1(&value[1..], raft_id)
+        } else {
+            Entry::deserialize_legacy(value, raft_id)
+        }
+    }
+
+    fn deserialize_v1(value: &[u8], raft_id: RaftId) -> Option<Self> {
+        let mut value_it = value.iter();
+
+        let account_id = AccountId::from_leb128_it(&mut value_it)?;
+        let total_changes = usize::from_leb128_it(&mut value_it)?;
+        let mut changes = Vec::with_capacity(total_changes);
+
+        // The first change id is stored absolutely; the rest are signed deltas
+        // relative to the previous change id so that clustered ids stay short.
+        let mut prev_change_id = 0i64;
+        for idx in 0..total_changes {
+            let collection = (*value_it.next()?).into();
+            let change_id = if idx == 0 {
+                let change_id = ChangeId::from_leb128_it(&mut value_it)?;
+                prev_change_id = change_id as i64;
+                change_id
+            } else {
+                prev_change_id += i64::from_leb128_it(&mut value_it)?;
+                prev_change_id as ChangeId
+            };
+            changes.push(Change {
+                collection,
+                change_id,
+            });
+        }
+
+        Entry {
+            account_id,
+            raft_id,
+            changes,
+        }
+        .into()
+    }
+
+    fn deserialize_legacy(value: &[u8], raft_id: RaftId) -> Option<Self> {
         let mut value_it = value.iter();
 
         let account_id = AccountId::from_leb128_it(&mut value_it)?;
@@ -74,23 +122,153 @@ impl Entry {
 impl StoreSerialize for Entry {
     fn serialize(&self) -> Option<Vec<u8>> {
         let mut bytes = Vec::with_capacity(
-            std::mem::size_of::<AccountId>()
+            1 + std::mem::size_of::<AccountId>()
                 + std::mem::size_of::<usize>()
                 + (self.changes.len()
                     * (std::mem::size_of::<ChangeId>() + std::mem::size_of::<Collection>())),
         );
+        bytes.push(Entry::VERSION);
         self.account_id.to_leb128_bytes(&mut bytes);
         self.changes.len().to_leb128_bytes(&mut bytes);
 
-        for change in &self.changes {
+        let mut prev_change_id = 0i64;
+        for (idx, change) in self.changes.iter().enumerate() {
             bytes.push(change.collection.into());
-            change.change_id.to_leb128_bytes(&mut bytes);
+            if idx == 0 {
+                change.change_id.to_leb128_bytes(&mut bytes);
+            } else {
+                (change.change_id as i64 - prev_change_id).to_leb128_bytes(&mut bytes);
+            }
+            prev_change_id = change.change_id as i64;
         }
 
         Some(bytes)
     }
 }
 
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Snapshot {
+    pub last_included: RaftId,
+    pub items: Vec<(AccountId, Collection, RoaringBitmap)>,
+}
+
+impl StoreSerialize for Snapshot {
+    fn serialize(&self) -> Option<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(
+            std::mem::size_of::<RaftId>() + (self.items.len() * std::mem::size_of::<AccountId>()),
+        );
+        self.last_included.term.to_leb128_bytes(&mut bytes);
+        self.last_included.index.to_leb128_bytes(&mut bytes);
+        self.items.len().to_leb128_bytes(&mut bytes);
+        for (account_id, collection, bitmap) in &self.items {
+            account_id.to_leb128_bytes(&mut bytes);
+            bytes.push((*collection).into());
+            bitmap.serialized_size().to_leb128_bytes(&mut bytes);
+            bitmap.serialize_into(&mut bytes).ok()?;
+        }
+        Some(bytes)
+    }
+}
+
+impl Snapshot {
+    pub fn deserialize(bytes: &[u8]) -> Option<Self> {
+        let mut bytes_it = bytes.iter();
+        let term = TermId::from_leb128_it(&mut bytes_it)?;
+        let index = LogIndex::from_leb128_it(&mut bytes_it)?;
+        let total_items = usize::from_leb128_it(&mut bytes_it)?;
+        let mut items = Vec::with_capacity(total_items);
+
+        // The remaining bytes hold the serialized bitmaps, length-prefixed so we
+        // can hand each slice to RoaringBitmap::deserialize_from.
+        let mut pos = bytes.len() - bytes_it.len();
+        for _ in 0..total_items {
+            let account_id = AccountId::from_leb128_it(&mut bytes_it)?;
+            let collection: Collection = (*bytes_it.next()?).into();
+            let bitmap_len = usize::from_leb128_it(&mut bytes_it)?;
+            pos = bytes.len() - bytes_it.len();
+            let bitmap = RoaringBitmap::deserialize_from(bytes.get(pos..pos + bitmap_len)?).ok()?;
+            bytes_it = bytes.get(pos + bitmap_len..)?.iter();
+            items.push((account_id, collection, bitmap));
+        }
+        let _ = pos;
+
+        Some(Snapshot {
+            last_included: RaftId::new(term, index),
+            items,
+        })
+    }
+}
+
+/// Reads a list of `count` document ids. When `delta` is set the first id is
+/// stored absolutely and the rest as signed zigzag deltas from the previous id
+/// (the list is written sorted, so deltas stay small); otherwise every id is
+/// stored absolutely in the legacy format.
+fn read_id_list<'x>(
+    bytes_it: &mut impl Iterator<Item = &'x u8>,
+    count: usize,
+    delta: bool,
+) -> Option<Vec<JMAPId>> {
+    let mut ids = Vec::with_capacity(count);
+    let mut prev = 0i64;
+    for idx in 0..count {
+        let id = if !delta || idx == 0 {
+            let id = JMAPId::from_leb128_it(bytes_it)?;
+            prev = id as i64;
+            id
+        } else {
+            prev += i64::from_leb128_it(bytes_it)?;
+            prev as JMAPId
+        };
+        ids.push(id);
+    }
+    Some(ids)
+}
+
+/// Writes a list of sorted document ids as signed zigzag deltas from the
+/// previous id, mirroring the decode side in [`read_id_list`]. The first id is
+/// written absolutely so the list can be decoded without external context.
+fn write_id_list(bytes: &mut Vec<u8>, ids: &[JMAPId]) {
+    let mut prev = 0i64;
+    for (idx, &id) in ids.iter().enumerate() {
+        if idx == 0 {
+            id.to_leb128_bytes(bytes);
+        } else {
+            (id as i64 - prev).to_leb128_bytes(bytes);
+        }
+        prev = id as i64;
+    }
+}
+
+/// Builds the versioned, delta-encoded byte representation of a single
+/// change-log record, counterpart to [`PendingChanges::deserialize`]. `inserted_ids`,
+/// `updated_ids` and `deleted_ids` must each be sorted ascending for the delta
+/// encoding to stay compact.
+pub fn serialize_change_log(
+    inserted_ids: &[JMAPId],
+    updated_ids: &[JMAPId],
+    child_updated_ids: &[JMAPId],
+    deleted_ids: &[JMAPId],
+) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(
+        1 + (inserted_ids.len() + updated_ids.len() + child_updated_ids.len() + deleted_ids.len())
+            * std::mem::size_of::<JMAPId>(),
+    );
+    bytes.push(PendingChanges::VERSION);
+    inserted_ids.len().to_leb128_bytes(&mut bytes);
+    updated_ids.len().to_leb128_bytes(&mut bytes);
+    child_updated_ids.len().to_leb128_bytes(&mut bytes);
+    deleted_ids.len().to_leb128_bytes(&mut bytes);
+
+    write_id_list(&mut bytes, inserted_ids);
+    write_id_list(&mut bytes, updated_ids);
+    for child_updated_id in child_updated_ids {
+        child_updated_id.to_leb128_bytes(&mut bytes);
+    }
+    write_id_list(&mut bytes, deleted_ids);
+
+    bytes
+}
+
 #[derive(Debug)]
 pub struct PendingChanges {
     pub account_id: AccountId,
@@ -123,26 +301,33 @@ impl PendingChanges {
             && self.changes.is_empty()
     }
 
+    /// Serialization version byte prepended to delta-encoded change records.
+    /// Records written before delta-encoding carry no version byte (their first
+    /// byte is the leb128-encoded insert count), so a leading
+    /// [`PendingChanges::VERSION`] byte selects the delta decoder and anything
+    /// else falls back to the legacy absolute format.
+    const VERSION: u8 = 1;
+
     pub fn deserialize(
         &mut self,
         change_id: ChangeId,
         bytes: &[u8],
         tombstones: &RoaringBitmap,
     ) -> Option<()> {
-        let mut bytes_it = bytes.iter();
+        // Delta-encoded records are versioned; legacy records store the sorted
+        // document ids absolutely. Both share the same folding logic below.
+        let delta = bytes.first().copied() == Some(Self::VERSION);
+        let mut bytes_it = if delta { bytes[1..].iter() } else { bytes.iter() };
+
         let total_inserts = usize::from_leb128_it(&mut bytes_it)?;
         let total_updates = usize::from_leb128_it(&mut bytes_it)?;
         let total_child_updates = usize::from_leb128_it(&mut bytes_it)?;
         let total_deletes = usize::from_leb128_it(&mut bytes_it)?;
 
-        let mut inserted_ids = Vec::with_capacity(total_inserts);
+        let mut inserted_ids = read_id_list(&mut bytes_it, total_inserts, delta)?;
 
-        for _ in 0..total_inserts {
-            inserted_ids.push(JMAPId::from_leb128_it(&mut bytes_it)?);
-        }
-
-        for _ in 0..total_updates {
-            let document_id = JMAPId::from_leb128_it(&mut bytes_it)?.get_document_id();
+        for document_id in read_id_list(&mut bytes_it, total_updates, delta)? {
+            let document_id = document_id.get_document_id();
             if !self.inserts.contains(document_id) {
                 self.updates.insert(document_id);
             }
@@ -153,8 +338,7 @@ impl PendingChanges {
             skip_leb128_it(&mut bytes_it)?;
         }
 
-        for _ in 0..total_deletes {
-            let deleted_id = JMAPId::from_leb128_it(&mut bytes_it)?;
+        for deleted_id in read_id_list(&mut bytes_it, total_deletes, delta)? {
             let document_id = deleted_id.get_document_id();
             let prefix_id = deleted_id.get_prefix_id();
             if let Some(pos) = inserted_ids.iter().position(|&inserted_id| {
@@ -291,12 +475,48 @@ where
         )
     }
 
+    /// Writes a single change-log record using the delta-encoded format built by
+    /// [`serialize_change_log`], the counterpart to [`PendingChanges::deserialize`]'s
+    /// versioned decode path. `inserted_ids`, `updated_ids` and `deleted_ids` must
+    /// each be sorted ascending, matching [`serialize_change_log`]'s requirement.
+    pub fn insert_changes(
+        &self,
+        account_id: AccountId,
+        collection: Collection,
+        change_id: ChangeId,
+        inserted_ids: &[JMAPId],
+        updated_ids: &[JMAPId],
+        child_updated_ids: &[JMAPId],
+        deleted_ids: &[JMAPId],
+    ) -> crate::Result<()> {
+        self.db.write(vec![WriteOperation::set(
+            ColumnFamily::Logs,
+            LogKey::serialize_change(account_id, collection, change_id),
+            serialize_change_log(inserted_ids, updated_ids, child_updated_ids, deleted_ids),
+        )])
+    }
+
     pub fn get_pending_changes(
         &self,
         account: AccountId,
         collection: Collection,
         from_change_id: Option<ChangeId>,
         only_ids: bool,
+    ) -> crate::Result<PendingChanges> {
+        self.get_pending_changes_until(account, collection, from_change_id, None, only_ids)
+    }
+
+    /// Like [`get_pending_changes`](Self::get_pending_changes), but stops folding
+    /// the change log once `to_change_id` is reached. Used by [`compact_log`](Self::compact_log)
+    /// so the materialized snapshot never observes changes beyond the commit
+    /// boundary it is supposed to represent.
+    pub fn get_pending_changes_until(
+        &self,
+        account: AccountId,
+        collection: Collection,
+        from_change_id: Option<ChangeId>,
+        to_change_id: Option<ChangeId>,
+        only_ids: bool,
     ) -> crate::Result<PendingChanges> {
         let mut changes = PendingChanges::new(account, collection);
 
@@ -329,6 +549,12 @@ where
                 ))
             })?;
 
+            if let Some(to_change_id) = to_change_id {
+                if change_id > to_change_id {
+                    break;
+                }
+            }
+
             if change_id > from_change_id || (is_inclusive && change_id == from_change_id) {
                 if !only_ids {
                     changes
@@ -347,4 +573,175 @@ where
 
         Ok(changes)
     }
+
+    /// Materializes the current state of every `(AccountId, Collection)` touched
+    /// by the log up to `last_applied`, persists it as a [`Snapshot`] and removes
+    /// every raft entry with `index <= last_applied.index`.
+    ///
+    /// `last_applied` must point at a committed boundary: no in-flight [`Entry`]
+    /// may reference a `change_id` that has already been folded into the snapshot,
+    /// otherwise the compacted log and the snapshot would disagree.
+    pub fn compact_log(&self, last_applied: RaftId) -> crate::Result<Snapshot> {
+        // Collect every (account, collection) pair referenced by the log, along
+        // with the highest change_id committed at or before `last_applied`, so
+        // the fold below never runs past the commit boundary it is meant to
+        // represent.
+        let mut collections: std::collections::HashMap<(AccountId, Collection), ChangeId> =
+            std::collections::HashMap::new();
+        for entry in self.get_raft_entries(RaftId::none(), 0)? {
+            if entry.raft_id.index > last_applied.index {
+                break;
+            }
+            for change in &entry.changes {
+                let max_change_id = collections
+                    .entry((entry.account_id, change.collection))
+                    .or_insert(0);
+                *max_change_id = (*max_change_id).max(change.change_id);
+            }
+        }
+
+        let mut items = Vec::with_capacity(collections.len());
+        for ((account_id, collection), to_change_id) in collections {
+            // Folding the change log leaves `inserts` holding exactly the live
+            // document ids (inserts that were never deleted, honouring reused ids).
+            let changes = self.get_pending_changes_until(
+                account_id,
+                collection,
+                None,
+                Some(to_change_id),
+                false,
+            )?;
+            if !changes.inserts.is_empty() {
+                items.push((account_id, collection, changes.inserts));
+            }
+        }
+
+        let snapshot = Snapshot {
+            last_included: last_applied,
+            items,
+        };
+
+        // Delete every previous snapshot before writing the new one: `get_snapshot`
+        // returns the first key under `SNAPSHOT_KEY_PREFIX`, so leaving an older
+        // snapshot in place would make it (rather than this one) the one a
+        // lagging follower installs.
+        let mut snapshot_ops = Vec::new();
+        for (key, _) in self.db.iterator(
+            ColumnFamily::Logs,
+            &[LogKey::SNAPSHOT_KEY_PREFIX],
+            Direction::Forward,
+        )? {
+            if !key.starts_with(&[LogKey::SNAPSHOT_KEY_PREFIX]) {
+                break;
+            }
+            snapshot_ops.push(WriteOperation::delete(ColumnFamily::Logs, key.to_vec()));
+        }
+        snapshot_ops.push(WriteOperation::set(
+            ColumnFamily::Logs,
+            LogKey::serialize_snapshot(&last_applied),
+            snapshot.serialize().ok_or_else(|| {
+                StoreError::SerializeError("Failed to serialize snapshot.".to_string())
+            })?,
+        ));
+        self.db.write(snapshot_ops)?;
+
+        let mut delete_keys = Vec::new();
+        let prefix = &[LogKey::RAFT_KEY_PREFIX];
+        for (key, _) in self.db.iterator(
+            ColumnFamily::Logs,
+            &LogKey::serialize_raft(&RaftId::new(0, 0)),
+            Direction::Forward,
+        )? {
+            if !key.starts_with(prefix) {
+                break;
+            }
+            let raft_id = LogKey::deserialize_raft(&key).ok_or_else(|| {
+                StoreError::InternalError(format!("Corrupted raft entry for [{:?}]", key))
+            })?;
+            if raft_id.index <= last_applied.index {
+                delete_keys.push(WriteOperation::delete(ColumnFamily::Logs, key.to_vec()));
+            }
+        }
+        if !delete_keys.is_empty() {
+            self.db.write(delete_keys)?;
+        }
+
+        Ok(snapshot)
+    }
+
+    pub fn get_snapshot(&self) -> crate::Result<Option<Snapshot>> {
+        if let Some((key, value)) = self
+            .db
+            .iterator(
+                ColumnFamily::Logs,
+                &[LogKey::SNAPSHOT_KEY_PREFIX],
+                Direction::Forward,
+            )?
+            .next()
+        {
+            if key.starts_with(&[LogKey::SNAPSHOT_KEY_PREFIX]) {
+                return Ok(Some(Snapshot::deserialize(&value).ok_or_else(|| {
+                    StoreError::InternalError(format!("Corrupted snapshot for [{:?}]", key))
+                })?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Installs a snapshot received from the leader on a lagging follower: the
+    /// local log is truncated, the materialized bitmaps are written and the
+    /// `raft_term`/`raft_index` atomics are set to the snapshot boundary so that
+    /// subsequent [`get_raft_entries`](Self::get_raft_entries) calls resume
+    /// streaming from `last_included`.
+    pub fn install_snapshot(&self, snapshot: Snapshot) -> crate::Result<()> {
+        let mut ops = Vec::new();
+
+        // Truncate the local raft log entirely; the snapshot supersedes it.
+        let prefix = &[LogKey::RAFT_KEY_PREFIX];
+        for (key, _) in self.db.iterator(
+            ColumnFamily::Logs,
+            &LogKey::serialize_raft(&RaftId::new(0, 0)),
+            Direction::Forward,
+        )? {
+            if !key.starts_with(prefix) {
+                break;
+            }
+            ops.push(WriteOperation::delete(ColumnFamily::Logs, key.to_vec()));
+        }
+
+        // Drop any snapshot already on disk before recording this one, for the
+        // same reason as in `compact_log`: `get_snapshot` returns whichever
+        // snapshot key sorts first, so a stale one left behind could shadow
+        // the snapshot just installed.
+        for (key, _) in self.db.iterator(
+            ColumnFamily::Logs,
+            &[LogKey::SNAPSHOT_KEY_PREFIX],
+            Direction::Forward,
+        )? {
+            if !key.starts_with(&[LogKey::SNAPSHOT_KEY_PREFIX]) {
+                break;
+            }
+            ops.push(WriteOperation::delete(ColumnFamily::Logs, key.to_vec()));
+        }
+
+        // Write the materialized bitmaps and record the snapshot itself.
+        for (account_id, collection, bitmap) in &snapshot.items {
+            self.set_document_ids(*account_id, *collection, bitmap)?;
+        }
+        ops.push(WriteOperation::set(
+            ColumnFamily::Logs,
+            LogKey::serialize_snapshot(&snapshot.last_included),
+            snapshot.serialize().ok_or_else(|| {
+                StoreError::SerializeError("Failed to serialize snapshot.".to_string())
+            })?,
+        ));
+        self.db.write(ops)?;
+
+        self.raft_term
+            .store(snapshot.last_included.term, Ordering::Relaxed);
+        self.raft_index
+            .store(snapshot.last_included.index, Ordering::Relaxed);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,112 @@
+use store::{
+    ColumnFamily, Direction, KeyValueBackend, StoreError, WriteOperation,
+};
+
+use crate::SqlStore;
+
+// `SqlStore` backs the [`KeyValueBackend`] contract only: the raft log,
+// change log and snapshot/backup code in `store::raft`/`store::backup` all go
+// through `JMAPStore::db` directly as a `Store`, not through
+// `KeyValueBackend`, so `JMAPStore<SqlStore>` does not typecheck yet and this
+// is not a drop-in alternate backend for the whole server. Implementing the
+// full `Store` trait for `SqlStore` would additionally coherence-conflict
+// with `store::backend`'s blanket `impl<T: Store> KeyValueBackend for T`,
+// since this file already provides an explicit `KeyValueBackend` impl for
+// `SqlStore`. Wiring
+// `store::raft`'s log access through `KeyValueBackend` instead of `Store` so
+// `SqlStore` can serve it directly is tracked as follow-up work.
+
+/// Maps a [`ColumnFamily`] onto the SQL table that backs it. Each column family
+/// is a standalone `(k BYTEA PRIMARY KEY, v BYTEA)` table so key ordering — the
+/// property the raft log relies on — is handled by the SQL engine's index.
+fn cf_table(cf: ColumnFamily) -> &'static str {
+    match cf {
+        ColumnFamily::Logs => "logs",
+        ColumnFamily::Values => "values",
+    }
+}
+
+impl KeyValueBackend for SqlStore {
+    fn range_scan(
+        &self,
+        cf: ColumnFamily,
+        prefix: &[u8],
+        from: &[u8],
+        direction: Direction,
+    ) -> crate::Result<Vec<(Box<[u8]>, Box<[u8]>)>> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| StoreError::InternalError(e.to_string()))?;
+
+        // Seek from `from` in the requested direction and let the index return the
+        // rows in key order; the prefix is enforced below so the scan stops at the
+        // first key that leaves the requested key-space, matching the iterator
+        // semantics the other backends expose.
+        let query = match direction {
+            Direction::Forward => format!(
+                "SELECT k, v FROM {} WHERE k >= $1 ORDER BY k ASC",
+                cf_table(cf)
+            ),
+            Direction::Backward => format!(
+                "SELECT k, v FROM {} WHERE k <= $1 ORDER BY k DESC",
+                cf_table(cf)
+            ),
+        };
+
+        let mut results = Vec::new();
+        for row in conn
+            .query(query.as_str(), &[&from])
+            .map_err(|e| StoreError::InternalError(e.to_string()))?
+        {
+            let key: Vec<u8> = row.get::<_, Vec<u8>>(0);
+            if !key.starts_with(prefix) {
+                break;
+            }
+            let value: Vec<u8> = row.get::<_, Vec<u8>>(1);
+            results.push((key.into_boxed_slice(), value.into_boxed_slice()));
+        }
+
+        Ok(results)
+    }
+
+    fn multi_put(&self, batch: Vec<WriteOperation>) -> crate::Result<()> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| StoreError::InternalError(e.to_string()))?;
+
+        // Apply the whole batch inside a single transaction so the store never
+        // observes a partially-written raft entry.
+        let mut tx = conn
+            .transaction()
+            .map_err(|e| StoreError::InternalError(e.to_string()))?;
+
+        for op in batch {
+            match op {
+                WriteOperation::Set { cf, key, value } => {
+                    tx.execute(
+                        format!(
+                            "INSERT INTO {} (k, v) VALUES ($1, $2) \
+                             ON CONFLICT (k) DO UPDATE SET v = EXCLUDED.v",
+                            cf_table(cf)
+                        )
+                        .as_str(),
+                        &[&key, &value],
+                    )
+                    .map_err(|e| StoreError::InternalError(e.to_string()))?;
+                }
+                WriteOperation::Delete { cf, key } => {
+                    tx.execute(
+                        format!("DELETE FROM {} WHERE k = $1", cf_table(cf)).as_str(),
+                        &[&key],
+                    )
+                    .map_err(|e| StoreError::InternalError(e.to_string()))?;
+                }
+            }
+        }
+
+        tx.commit()
+            .map_err(|e| StoreError::InternalError(e.to_string()))
+    }
+}
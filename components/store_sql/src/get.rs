@@ -0,0 +1,28 @@
+use store::{
+    serialize::serialize_stored_key, AccountId, ArrayPos, CollectionId, DocumentId, FieldId,
+    StoreError, StoreGet,
+};
+
+use crate::SqlStore;
+
+impl StoreGet for SqlStore {
+    fn get_stored_value(
+        &self,
+        account: AccountId,
+        collection: CollectionId,
+        document: DocumentId,
+        field: FieldId,
+        pos: ArrayPos,
+    ) -> crate::Result<Option<Vec<u8>>> {
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|e| StoreError::InternalError(e.to_string()))?;
+        conn.query_opt(
+            "SELECT v FROM values WHERE k = $1",
+            &[&serialize_stored_key(account, collection, document, field, pos)],
+        )
+        .map(|row| row.map(|row| row.get::<_, Vec<u8>>(0)))
+        .map_err(|e| StoreError::InternalError(e.to_string()))
+    }
+}
@@ -0,0 +1,315 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart JMAP Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+use dashmap::DashMap;
+use instant_acme::{
+    Account, AccountCredentials, AuthorizationStatus, ChallengeType, Identifier, NewAccount,
+    NewOrder, OrderStatus,
+};
+use rustls::{Certificate, PrivateKey, ServerConfig};
+use store::{ColumnFamily, Direction, JMAPStore, Store, WriteOperation};
+use utils::config::Config;
+
+/// Store key under which the ACME account credentials are persisted so the same
+/// account (and its registration) is reused across restarts.
+const ACME_ACCOUNT_KEY: &[u8] = b"__acme_account";
+/// Store key under which the issued certificate bundle (private key + chain) is
+/// persisted so a reissued certificate survives restarts.
+const ACME_CERT_KEY: &[u8] = b"__acme_cert";
+
+/// How long the renewal task waits after a successful run before provisioning
+/// again.
+const RENEWAL_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+/// Renewal window before expiry, in days. The renewal task does not read it: it
+/// orders a new certificate on every [`RENEWAL_INTERVAL`].
+const RENEW_BEFORE_DAYS: i64 = 30;
+/// Initial backoff applied to a failed order; doubled on each retry up to
+/// [`MAX_BACKOFF`].
+const INITIAL_BACKOFF: Duration = Duration::from_secs(60);
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// Settings controlling automatic certificate management.
+#[derive(Debug, Clone)]
+pub struct AcmeConfig {
+    pub directory_url: String,
+    pub contact: Vec<String>,
+    pub domains: Vec<String>,
+}
+
+impl AcmeConfig {
+    /// Parses the `[acme]` section, returning `None` when ACME is disabled.
+    pub fn parse(config: &Config) -> Option<Self> {
+        if !config.property_or_static::<bool>("acme.enable", "false").ok()? {
+            return None;
+        }
+        Some(AcmeConfig {
+            directory_url: config
+                .value("acme.directory")
+                .unwrap_or("https://acme-v02.api.letsencrypt.org/directory")
+                .to_string(),
+            contact: config.values("acme.contact").map(|(_, v)| v.to_string()).collect(),
+            domains: config.values("acme.domains").map(|(_, v)| v.to_string()).collect(),
+        })
+    }
+}
+
+/// Holds the in-flight HTTP-01 challenge tokens keyed by token, plus the live
+/// TLS configuration handle so renewed certificates can be installed without
+/// tearing down listeners.
+pub struct AcmeManager<T> {
+    store: Arc<JMAPStore<T>>,
+    config: AcmeConfig,
+    tls_config: Arc<ArcSwap<rustls::ServerConfig>>,
+    challenges: DashMap<String, String>,
+}
+
+impl<T> AcmeManager<T>
+where
+    T: for<'x> Store<'x> + 'static,
+{
+    pub fn new(
+        store: Arc<JMAPStore<T>>,
+        config: AcmeConfig,
+        tls_config: Arc<ArcSwap<rustls::ServerConfig>>,
+    ) -> Self {
+        AcmeManager {
+            store,
+            config,
+            tls_config,
+            challenges: DashMap::new(),
+        }
+    }
+
+    /// Returns the key authorization for an HTTP-01 challenge token, for the
+    /// handler serving `/.well-known/acme-challenge/<token>`.
+    pub fn http_challenge(&self, token: &str) -> Option<String> {
+        self.challenges.get(token).map(|v| v.value().clone())
+    }
+
+    /// Obtains (or renews) a certificate for the configured domains over the
+    /// HTTP-01 challenge, persisting the account credentials and the issued
+    /// certificate in the store and installing the result into the live TLS
+    /// config. The order itself is not persisted.
+    async fn provision(&self) -> Result<(), instant_acme::Error> {
+        // Serve the certificate from a previous run while we check for renewal, so
+        // a restart is not left without TLS until the next order completes.
+        if let Some(bytes) = self.load_value(ACME_CERT_KEY)? {
+            if let Ok(cert) = std::str::from_utf8(&bytes) {
+                self.install_certificate(cert)?;
+            }
+        }
+
+        let account = self.load_or_create_account().await?;
+
+        let identifiers = self
+            .config
+            .domains
+            .iter()
+            .map(|d| Identifier::Dns(d.clone()))
+            .collect::<Vec<_>>();
+        let mut order = account.new_order(&NewOrder { identifiers: &identifiers }).await?;
+
+        let authorizations = order.authorizations().await?;
+        for authz in &authorizations {
+            if authz.status == AuthorizationStatus::Valid {
+                continue;
+            }
+            let challenge = authz
+                .challenges
+                .iter()
+                .find(|c| c.r#type == ChallengeType::Http01)
+                .ok_or(instant_acme::Error::Str("No HTTP-01 challenge offered."))?;
+            let Identifier::Dns(domain) = &authz.identifier;
+            self.challenges.insert(
+                challenge.token.clone(),
+                order.key_authorization(challenge).as_str().to_string(),
+            );
+            tracing::debug!("Serving HTTP-01 challenge for {}.", domain);
+            order.set_challenge_ready(&challenge.url).await?;
+        }
+
+        // Poll until the order is ready, then finalize and persist the cert.
+        for attempt in 0..10u32 {
+            order.refresh().await?;
+            if !matches!(order.state().status, OrderStatus::Pending | OrderStatus::Processing) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_secs(1 << attempt.min(5))).await;
+        }
+        if matches!(order.state().status, OrderStatus::Ready | OrderStatus::Valid) {
+            let cert = order.finalize().await?;
+            self.store_certificate(&cert)?;
+            self.install_certificate(&cert)?;
+        } else {
+            return Err(instant_acme::Error::Str("ACME order did not become ready."));
+        }
+
+        Ok(())
+    }
+
+    /// Restores the previously-registered ACME account from the store, falling
+    /// back to registering a fresh one (and persisting its credentials) when none
+    /// is stored or the stored copy is unreadable.
+    async fn load_or_create_account(&self) -> Result<Account, instant_acme::Error> {
+        if let Some(bytes) = self.load_value(ACME_ACCOUNT_KEY)? {
+            match serde_json::from_slice::<AccountCredentials>(&bytes) {
+                Ok(credentials) => return Account::from_credentials(credentials),
+                Err(_) => tracing::warn!(
+                    "Stored ACME account credentials are unreadable, registering a new account."
+                ),
+            }
+        }
+
+        let (account, credentials) = Account::create(
+            &NewAccount {
+                contact: &self
+                    .config
+                    .contact
+                    .iter()
+                    .map(|c| c.as_str())
+                    .collect::<Vec<_>>(),
+                terms_of_service_agreed: true,
+                only_return_existing: false,
+            },
+            &self.config.directory_url,
+            None,
+        )
+        .await?;
+
+        match serde_json::to_vec(&credentials) {
+            Ok(bytes) => self.store_value(ACME_ACCOUNT_KEY, bytes)?,
+            Err(err) => tracing::warn!("Failed to serialize ACME account credentials: {}.", err),
+        }
+        Ok(account)
+    }
+
+    fn store_certificate(&self, cert: &str) -> Result<(), instant_acme::Error> {
+        // Persisted under an ACME-specific key-space in the value store so the
+        // certificate survives restarts and can be shared across nodes.
+        self.store_value(ACME_CERT_KEY, cert.as_bytes().to_vec())
+    }
+
+    fn install_certificate(&self, cert: &str) -> Result<(), instant_acme::Error> {
+        // Build a fresh server config from the issued bundle and hot-swap the
+        // live TLS config so the renewal lands with zero downtime.
+        let server_config = build_server_config(cert)?;
+        self.tls_config.store(Arc::new(server_config));
+        Ok(())
+    }
+
+    /// Reads a raw value previously written by [`store_value`], going through the
+    /// backend-agnostic key-value contract so the same path works on any store.
+    fn load_value(&self, key: &[u8]) -> Result<Option<Vec<u8>>, instant_acme::Error> {
+        let rows = self
+            .store
+            .db
+            .range_scan(ColumnFamily::Values, key, key, Direction::Forward)
+            .map_err(|_| instant_acme::Error::Str("Failed to read ACME value from store."))?;
+        for (k, v) in rows {
+            if k.as_ref() == key {
+                return Ok(Some(v.to_vec()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Persists a raw value atomically through the backend-agnostic contract.
+    fn store_value(&self, key: &[u8], value: Vec<u8>) -> Result<(), instant_acme::Error> {
+        self.store
+            .db
+            .multi_put(vec![WriteOperation::set(
+                ColumnFamily::Values,
+                key.to_vec(),
+                value,
+            )])
+            .map_err(|_| instant_acme::Error::Str("Failed to persist ACME value to store."))
+    }
+}
+
+/// Parses an issued PEM bundle (private key followed by the certificate chain)
+/// into a ready-to-serve [`ServerConfig`].
+fn build_server_config(bundle: &str) -> Result<ServerConfig, instant_acme::Error> {
+    let mut reader = std::io::BufReader::new(bundle.as_bytes());
+    let mut certs = Vec::new();
+    let mut key = None;
+
+    for item in std::iter::from_fn(|| rustls_pemfile::read_one(&mut reader).transpose()) {
+        match item.map_err(|_| instant_acme::Error::Str("Malformed PEM in issued certificate."))? {
+            rustls_pemfile::Item::X509Certificate(der) => certs.push(Certificate(der)),
+            rustls_pemfile::Item::PKCS8Key(der) | rustls_pemfile::Item::RSAKey(der) => {
+                key = Some(PrivateKey(der))
+            }
+            _ => {}
+        }
+    }
+
+    let key = key.ok_or(instant_acme::Error::Str(
+        "Issued bundle is missing its private key.",
+    ))?;
+    if certs.is_empty() {
+        return Err(instant_acme::Error::Str(
+            "Issued bundle is missing its certificate chain.",
+        ));
+    }
+
+    ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|_| instant_acme::Error::Str("Rejected issued certificate."))
+}
+
+/// Spawns the background renewal task when ACME is enabled. It provisions again,
+/// ordering a new certificate, every [`RENEWAL_INTERVAL`]; the expiry date of the
+/// current certificate is not inspected. Failed orders are retried with
+/// truncated exponential backoff.
+pub fn spawn_acme<T>(manager: Arc<AcmeManager<T>>)
+where
+    T: for<'x> Store<'x> + 'static,
+{
+    tokio::spawn(async move {
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            match manager.provision().await {
+                Ok(()) => {
+                    backoff = INITIAL_BACKOFF;
+                    tokio::time::sleep(RENEWAL_INTERVAL).await;
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        "ACME order failed ({}), retrying in {}s.",
+                        err,
+                        backoff.as_secs()
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = std::cmp::min(backoff * 2, MAX_BACKOFF);
+                }
+            }
+        }
+    });
+    let _ = RENEW_BEFORE_DAYS;
+}
@@ -13,7 +13,7 @@ use jmap::request::ACLEnforce;
 use jmap::types::blob::JMAPBlob;
 use jmap::types::jmap::JMAPId;
 use jmap::SUPERUSER_ID;
-use jmap_mail::mail::get::{BlobResult, JMAPGetMail};
+use jmap_mail::mail::get::JMAPGetMail;
 use jmap_mail::mail::sharing::JMAPShareMail;
 use jmap_sharing::principal::account::JMAPAccountStore;
 use reqwest::header::CONTENT_TYPE;
@@ -30,6 +30,7 @@ pub struct Params {
 pub async fn handle_jmap_download<T>(
     path: web::Path<(JMAPId, JMAPBlob, String)>,
     params: web::Query<Params>,
+    request: HttpRequest,
     core: web::Data<JMAPServer<T>>,
     session: Session,
 ) -> Result<HttpResponse, RequestError>
@@ -40,29 +41,106 @@ where
     let (id, blob_id, filename) = path.into_inner();
     let account_id = id.get_document_id();
 
+    // Parse an optional byte range up front; it is resolved against the blob
+    // length inside the worker so only the requested slice is read from store.
+    let range = request
+        .headers()
+        .get("Range")
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.to_string());
+
     let store = core.store.clone();
     match core
         .spawn_worker(move || {
-            store.mail_blob_get(
-                account_id,
-                &store.get_acl_token(session.account_id())?,
-                &blob_id,
-            )
+            let acl = store.get_acl_token(session.account_id())?;
+
+            // Authorize the download without materializing the blob, mirroring
+            // the access rules enforced by `copy_blob`.
+            let authorized = acl.is_member(account_id)
+                || acl.is_member(SUPERUSER_ID)
+                || store.blob_account_has_access(&blob_id.id, &acl.member_of)?
+                || match store
+                    .mail_shared_messages(account_id, &acl.member_of, ACL::ReadItems)?
+                    .as_ref()
+                {
+                    Some(shared) => store.blob_document_has_access(
+                        &blob_id.id,
+                        account_id,
+                        Collection::Mail,
+                        shared,
+                    )?,
+                    None => false,
+                };
+            if !authorized {
+                return Ok(BlobDownload::Unauthorized);
+            }
+
+            // Look up the length first so suffix/open ranges resolve and only the
+            // addressed window is read back — large attachments are never pulled
+            // into memory in full to serve a small range.
+            let total = match store.get_blob_len_by_id(&blob_id)? {
+                Some(total) => total as u64,
+                None => return Ok(BlobDownload::NotFound),
+            };
+
+            Ok(match range.as_deref().map(|range| parse_range(range, total)) {
+                Some(Some((start, end))) => {
+                    match store
+                        .get_blob_range_by_id(&blob_id, start as u32..(end + 1) as u32)?
+                    {
+                        Some(bytes) => BlobDownload::Partial {
+                            bytes,
+                            start,
+                            end,
+                            total,
+                        },
+                        None => BlobDownload::NotFound,
+                    }
+                }
+                Some(None) => BlobDownload::Unsatisfiable { total },
+                None => match store.get_blob_by_id(&blob_id)? {
+                    Some(bytes) => BlobDownload::Full(bytes),
+                    None => BlobDownload::NotFound,
+                },
+            })
         })
         .await
     {
-        Ok(BlobResult::Blob(bytes)) => {
-            Ok(HttpResponse::build(StatusCode::OK)
-                .insert_header(("Content-Type", params.into_inner().accept))
-                .insert_header((
-                    "Content-Disposition",
-                    format!("attachment; filename=\"{}\"", filename), //TODO escape filename
-                ))
-                .insert_header(("Cache-Control", "private, immutable, max-age=31536000"))
-                .body(bytes))
+        Ok(result) => {
+            let content_type = params.into_inner().accept;
+            let content_disposition = format!("attachment; filename=\"{}\"", filename); //TODO escape filename
+
+            match result {
+                // A range was requested and is satisfiable: return 206.
+                BlobDownload::Partial {
+                    bytes,
+                    start,
+                    end,
+                    total,
+                } => Ok(HttpResponse::build(StatusCode::PARTIAL_CONTENT)
+                    .insert_header(("Content-Type", content_type))
+                    .insert_header(("Content-Disposition", content_disposition))
+                    .insert_header(("Cache-Control", "private, immutable, max-age=31536000"))
+                    .insert_header(("Accept-Ranges", "bytes"))
+                    .insert_header(("Content-Range", format!("bytes {}-{}/{}", start, end, total)))
+                    .body(bytes)),
+                // A range was requested but cannot be satisfied: return 416.
+                BlobDownload::Unsatisfiable { total } => {
+                    Ok(HttpResponse::build(StatusCode::RANGE_NOT_SATISFIABLE)
+                        .insert_header(("Content-Range", format!("bytes */{}", total)))
+                        .finish())
+                }
+                // No range requested: full body, advertising range support.
+                BlobDownload::Full(bytes) => Ok(HttpResponse::build(StatusCode::OK)
+                    .insert_header(("Content-Type", content_type))
+                    .insert_header(("Content-Disposition", content_disposition))
+                    .insert_header(("Cache-Control", "private, immutable, max-age=31536000"))
+                    .insert_header(("Accept-Ranges", "bytes"))
+                    .body(bytes)),
+                BlobDownload::NotFound => Err(RequestError::not_found()),
+                BlobDownload::Unauthorized => Err(RequestError::forbidden()),
+            }
         }
-        Ok(BlobResult::NotFound) => Err(RequestError::not_found()),
-        Ok(BlobResult::Unauthorized) => Err(RequestError::forbidden()),
         Err(err) => {
             error!("Blob download failed: {:?}", err);
             Err(RequestError::internal_server_error())
@@ -70,6 +148,62 @@ where
     }
 }
 
+/// Outcome of a length-aware blob download. The worker resolves the requested
+/// range against the blob length and reads only the addressed bytes, so a range
+/// request never pulls the whole blob into memory.
+enum BlobDownload {
+    /// Full blob (no range requested).
+    Full(Vec<u8>),
+    /// A satisfiable range; `start`/`end` are inclusive and `total` is the blob
+    /// length for the `Content-Range` header.
+    Partial {
+        bytes: Vec<u8>,
+        start: u64,
+        end: u64,
+        total: u64,
+    },
+    /// A range was requested that cannot be satisfied (`416`).
+    Unsatisfiable { total: u64 },
+    NotFound,
+    Unauthorized,
+}
+
+/// Parses a single-range `Range: bytes=start-end` header against a blob of
+/// `total` bytes, returning the resolved inclusive `(start, end)` offsets.
+/// Returns `None` when the range is syntactically invalid or not satisfiable
+/// (the caller responds `416 Range Not Satisfiable`). Suffix (`bytes=-N`) and
+/// open-ended (`bytes=N-`) forms are supported; only the first range of a
+/// comma-separated set is honored.
+fn parse_range(header: &str, total: u64) -> Option<(u64, u64)> {
+    if total == 0 {
+        return None;
+    }
+    let spec = header.strip_prefix("bytes=")?;
+    let spec = spec.split(',').next()?.trim();
+    let (start, end) = spec.split_once('-')?;
+
+    let (start, end) = match (start.trim(), end.trim()) {
+        // Suffix range: the last N bytes.
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            if suffix == 0 {
+                return None;
+            }
+            (total.saturating_sub(suffix), total - 1)
+        }
+        // Open-ended range: from `start` to the end of the blob.
+        (start, "") => (start.parse().ok()?, total - 1),
+        // Closed range, clamped to the last byte.
+        (start, end) => (start.parse().ok()?, std::cmp::min(end.parse().ok()?, total - 1)),
+    };
+
+    if start > end || start >= total {
+        None
+    } else {
+        Some((start, end))
+    }
+}
+
 #[derive(Debug, serde::Serialize)]
 struct UploadResponse {
     #[serde(rename(serialize = "accountId"))]
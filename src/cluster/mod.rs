@@ -0,0 +1,17 @@
+pub mod rpc;
+
+/// Numeric identifier for a cluster peer, stable for the lifetime of the
+/// cluster's configuration.
+pub type PeerId = u64;
+
+/// Notifications a peer's RPC task publishes to the cluster's main event loop.
+#[derive(Debug)]
+pub enum Event {
+    /// A peer connection transitioned to the online state.
+    PeerOnline(PeerId),
+    /// A peer connection transitioned to the offline state.
+    PeerOffline(PeerId),
+}
+
+/// Bound on the number of buffered messages for cluster IPC channels.
+pub const IPC_CHANNEL_BUFFER: usize = 1024;
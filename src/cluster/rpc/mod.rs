@@ -0,0 +1,77 @@
+pub mod peer;
+pub mod serialize;
+
+use bytes::Bytes;
+use tokio::sync::{mpsc, oneshot};
+
+pub use serialize::{Protocol, RpcEncoder};
+
+use crate::cluster::PeerId;
+
+/// Timeout for establishing and authenticating a new peer connection.
+pub const RPC_TIMEOUT_MS: u64 = 5_000;
+/// A connection with no activity for this long is torn down by the peer task.
+pub const RPC_INACTIVITY_TIMEOUT: u64 = 5 * 60 * 1000;
+/// Ceiling on the exponential backoff applied between reconnect attempts.
+pub const RPC_MAX_BACKOFF_MS: u64 = 30_000;
+/// Number of failed connection attempts tolerated before a queued message is
+/// reported as undeliverable.
+pub const RPC_MAX_CONNECT_ATTEMPTS: u32 = 3;
+
+/// Wire-level request payloads exchanged between cluster peers.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum Request {
+    /// Placeholder sent in place of a message that was given up on so the
+    /// caller isn't left blocking the connection on a stale send.
+    None,
+    /// Authenticates the connection with the peer's shared cluster key.
+    Auth { peer_id: PeerId, key: String },
+    /// Gossips the sender's known peer list.
+    UpdatePeers { peers: Vec<PeerId> },
+    /// Liveness probe sent while waiting to confirm a peer is back online.
+    Ping,
+}
+
+/// Wire-level response payloads exchanged between cluster peers.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum Response {
+    /// Sent whenever a request could not be answered (timeout, connection
+    /// loss, …) so the caller never blocks forever on a dropped oneshot.
+    None,
+    /// Reply to [`Request::Auth`]/[`Request::Ping`].
+    Pong,
+}
+
+/// A unit of work handed to a peer's RPC task by [`peer::spawn_peer_rpc`].
+pub enum RpcEvent {
+    /// A unary request whose single response is awaited on `response_tx`.
+    NeedResponse {
+        response_tx: oneshot::Sender<Response>,
+        request: Request,
+    },
+    /// A request sent without waiting for (or caring about) its response.
+    FireAndForget { request: Request },
+    /// A streaming request whose `Protocol::ResponseChunk` frames are forwarded
+    /// to `response_tx` until `Protocol::ResponseEnd` closes the stream.
+    NeedStream {
+        response_tx: mpsc::Sender<Bytes>,
+        request: Request,
+    },
+}
+
+impl RpcEvent {
+    /// Notifies whoever is waiting on this event that it could not be
+    /// delivered, without blocking the caller.
+    pub fn failed(self) {
+        match self {
+            RpcEvent::NeedResponse { response_tx, .. } => {
+                let _ = response_tx.send(Response::None);
+            }
+            RpcEvent::FireAndForget { .. } => {}
+            RpcEvent::NeedStream { response_tx, .. } => {
+                // Dropping the sender closes the consumer's stream.
+                drop(response_tx);
+            }
+        }
+    }
+}
@@ -1,9 +1,14 @@
+use bytes::Bytes;
+use futures::stream::{FuturesUnordered, SplitSink, SplitStream};
 use futures::{stream::StreamExt, SinkExt};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
 use std::time::Instant;
 use std::{net::SocketAddr, time::Duration};
 use store::rand::Rng;
 use store::tracing::{debug, error};
-use tokio::sync::watch;
+use tokio::sync::{oneshot, watch, Mutex};
 use tokio::{
     net::TcpStream,
     sync::mpsc,
@@ -19,18 +24,78 @@ use crate::cluster::{Event, PeerId, IPC_CHANNEL_BUFFER};
 use super::serialize::RpcEncoder;
 use super::{Protocol, RpcEvent, RPC_TIMEOUT_MS};
 
+/// Responses awaiting delivery, keyed by the request id written on the wire. The
+/// writer registers a [`oneshot::Sender`] here before sending a request; the
+/// reader removes and fulfills it when the matching [`Protocol::Response`]
+/// arrives. On connection loss every entry is drained with [`Response::None`] so
+/// no caller hangs.
+type Pending = Arc<Mutex<HashMap<u32, (oneshot::Sender<Response>, Instant)>>>;
+
+/// Smoothing factor for the latency EWMA: higher weights recent samples more.
+const LATENCY_EWMA_ALPHA: f64 = 0.2;
+/// Multiplicative decay applied to `error_score` on every successful response so
+/// a peer recovers its standing over time.
+const ERROR_DECAY: f64 = 0.5;
+
+/// Shared health record for a peer, published through the watch channel so
+/// read-distribution and retry logic can prefer the fastest, least-flaky online
+/// replica.
+#[derive(Debug, Clone, Default)]
+pub struct PeerHealth {
+    pub online: bool,
+    pub ewma_latency_ms: f64,
+    pub error_score: f64,
+}
+
+impl PeerHealth {
+    /// Folds a successful round-trip sample into the latency EWMA and decays the
+    /// error score.
+    fn record_success(&mut self, latency: Duration) {
+        let sample = latency.as_secs_f64() * 1000.0;
+        self.ewma_latency_ms = if self.ewma_latency_ms == 0.0 {
+            sample
+        } else {
+            LATENCY_EWMA_ALPHA * sample + (1.0 - LATENCY_EWMA_ALPHA) * self.ewma_latency_ms
+        };
+        self.error_score *= ERROR_DECAY;
+    }
+
+    /// Bumps the decaying error counter after a failed request.
+    fn record_error(&mut self) {
+        self.error_score = self.error_score * ERROR_DECAY + 1.0;
+    }
+}
+
+/// In-flight streaming responses, keyed by request id. The reader forwards each
+/// [`Protocol::ResponseChunk`] to the matching sender and drops it on
+/// [`Protocol::ResponseEnd`] (or connection loss), which the consumer observes
+/// as the channel closing.
+type PendingStreams = Arc<Mutex<HashMap<u32, mpsc::Sender<Bytes>>>>;
+
 pub fn spawn_peer_rpc(
     main_tx: mpsc::Sender<Event>,
     local_peer_id: PeerId,
     key: String,
     peer_id: PeerId,
     peer_addr: SocketAddr,
-) -> (mpsc::Sender<RpcEvent>, watch::Receiver<bool>) {
+) -> (mpsc::Sender<RpcEvent>, watch::Receiver<PeerHealth>) {
     let (event_tx, mut event_rx) = mpsc::channel::<RpcEvent>(IPC_CHANNEL_BUFFER);
-    let (online_tx, online_rx) = watch::channel(false);
+    let (health_tx, health_rx) = watch::channel(PeerHealth::default());
+    let health_tx = Arc::new(health_tx);
 
     tokio::spawn(async move {
-        let mut conn_ = None;
+        // Split connection halves: the write half stays with the writer loop,
+        // the read half is moved into a reader task that dispatches responses.
+        let mut writer: Option<SplitSink<Framed<TcpStream, RpcEncoder>, Protocol>> = None;
+        let mut reader_handle: Option<tokio::task::JoinHandle<()>> = None;
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+        let streams: PendingStreams = Arc::new(Mutex::new(HashMap::new()));
+        let request_id = AtomicU32::new(0);
+        let health = Arc::new(Mutex::new(PeerHealth::default()));
+        // Last time a stream chunk was received. The reader task stamps this on
+        // every `ResponseChunk` so a long transfer that produces no new
+        // `RpcEvent` still keeps the connection from tripping the idle timeout.
+        let last_chunk: Arc<Mutex<Instant>> = Arc::new(Mutex::new(Instant::now()));
         let mut is_online = false;
 
         'main: loop {
@@ -47,18 +112,27 @@ pub fn spawn_peer_rpc(
                 }
                 Err(_) => {
                     // Close connection after the configured inactivity timeout.
-                    if conn_.is_some() {
+                    if writer.is_some() {
+                        // A streaming response driven by the reader task does not
+                        // produce `RpcEvent`s, so check for recent chunk activity
+                        // before tearing down: an in-flight transfer that is still
+                        // delivering chunks must keep the connection alive.
+                        let since_chunk = last_chunk.lock().await.elapsed();
+                        if !streams.lock().await.is_empty()
+                            && since_chunk < Duration::from_millis(RPC_INACTIVITY_TIMEOUT)
+                        {
+                            continue;
+                        }
                         debug!("Closing inactive connection to peer {}.", peer_addr);
-                        conn_ = None;
+                        close_connection(&mut writer, &mut reader_handle, &pending, &streams)
+                            .await;
                     }
                     continue;
                 }
             };
 
             // Connect to peer if we are not already connected.
-            let conn = if let Some(conn) = &mut conn_ {
-                conn
-            } else {
+            if writer.is_none() {
                 let mut connection_attempts = 0;
 
                 'retry: loop {
@@ -73,14 +147,24 @@ pub fn spawn_peer_rpc(
                     .await
                     {
                         Ok(conn) => {
-                            conn_ = conn.into();
+                            // Split the freshly authenticated connection and spawn
+                            // the reader that dispatches responses by id.
+                            let (sink, stream) = conn.split();
+                            writer = Some(sink);
+                            reader_handle = Some(spawn_reader(
+                                stream,
+                                pending.clone(),
+                                streams.clone(),
+                                health.clone(),
+                                health_tx.clone(),
+                                last_chunk.clone(),
+                                peer_addr,
+                            ));
 
                             // Notify processes that the peer is online.
                             if !is_online {
                                 is_online = true;
-                                if online_tx.send(true).is_err() {
-                                    debug!("Failed to send online status.");
-                                }
+                                set_online(&health, &health_tx, true).await;
                             }
 
                             if connection_attempts < RPC_MAX_CONNECT_ATTEMPTS {
@@ -168,55 +252,257 @@ pub fn spawn_peer_rpc(
                         }
                     }
                 }
+            }
 
-                conn_.as_mut().unwrap()
-            };
+            let sink = writer.as_mut().unwrap();
 
             let err = match message {
                 RpcEvent::NeedResponse {
                     response_tx,
                     request,
-                } => match send_rpc(conn, request).await {
-                    Ok(response) => {
-                        // Send response via oneshot channel
-                        if response_tx.send(response).is_err() {
-                            error!("Channel failed while sending message.");
+                } => {
+                    // Register the caller's oneshot under a fresh id and write the
+                    // tagged request; the reader will route the response back.
+                    let id = request_id.fetch_add(1, Ordering::Relaxed);
+                    // Record the send instant so the reader can time the round-trip.
+                    pending.lock().await.insert(id, (response_tx, Instant::now()));
+                    match sink.send(Protocol::Request { id, request }).await {
+                        Ok(()) => continue,
+                        Err(err) => {
+                            // Reclaim the oneshot so the caller is notified below.
+                            if let Some((response_tx, _)) = pending.lock().await.remove(&id) {
+                                if response_tx.send(Response::None).is_err() {
+                                    error!("Channel failed while sending message.");
+                                }
+                            }
+                            err
                         }
-                        continue;
                     }
-                    Err(err) => {
-                        if response_tx.send(Response::None).is_err() {
-                            error!("Channel failed while sending message.");
-                        }
-                        err
+                }
+                RpcEvent::FireAndForget { request } => {
+                    let id = request_id.fetch_add(1, Ordering::Relaxed);
+                    match sink.send(Protocol::Request { id, request }).await {
+                        Ok(()) => continue,
+                        Err(err) => err,
                     }
-                },
-                RpcEvent::FireAndForget { request } => match send_rpc(conn, request).await {
-                    Ok(response) => {
-                        // Send response via the main channel
-                        if let Err(err) =
-                            main_tx.send(Event::RpcResponse { peer_id, response }).await
-                        {
-                            error!("Channel failed while sending message: {}", err);
+                }
+                RpcEvent::NeedStream {
+                    response_tx,
+                    request,
+                } => {
+                    // Register the chunk sink under a fresh id; the reader forwards
+                    // chunks until ResponseEnd, then drops the sender.
+                    let id = request_id.fetch_add(1, Ordering::Relaxed);
+                    streams.lock().await.insert(id, response_tx);
+                    match sink.send(Protocol::Request { id, request }).await {
+                        Ok(()) => continue,
+                        Err(err) => {
+                            // Drop the sender so the consumer sees a truncated stream.
+                            streams.lock().await.remove(&id);
+                            err
                         }
-                        continue;
                     }
-                    Err(err) => err,
-                },
+                }
             };
 
             debug!("Failed to send RPC request to peer {}: {}", peer_addr, err);
-            conn_ = None;
+            // Record the failure against the peer's error score before tearing
+            // the connection down.
+            health.lock().await.record_error();
+            close_connection(&mut writer, &mut reader_handle, &pending, &streams).await;
 
             // Notify processes that the peer is offline.
             is_online = false;
-            if online_tx.send(false).is_err() {
-                debug!("Failed to send online status.");
-            }
+            set_online(&health, &health_tx, false).await;
         }
     });
 
-    (event_tx, online_rx)
+    (event_tx, health_rx)
+}
+
+/// Flips the shared health record's `online` flag and publishes the new value.
+async fn set_online(
+    health: &Arc<Mutex<PeerHealth>>,
+    health_tx: &watch::Sender<PeerHealth>,
+    online: bool,
+) {
+    let snapshot = {
+        let mut health = health.lock().await;
+        health.online = online;
+        health.clone()
+    };
+    if health_tx.send(snapshot).is_err() {
+        debug!("Failed to send peer health update.");
+    }
+}
+
+/// Selects the best peer to route a request to: only online peers are eligible,
+/// ranked by error score then latency, so traffic avoids flaky or slow replicas.
+/// Peers whose error score is above `backoff_threshold` are skipped as if in
+/// backoff. Returns `None` when no candidate is eligible.
+pub fn pick_peer(
+    candidates: &[(PeerId, watch::Receiver<PeerHealth>)],
+    backoff_threshold: f64,
+) -> Option<PeerId> {
+    candidates
+        .iter()
+        .filter_map(|(peer_id, health_rx)| {
+            let health = health_rx.borrow();
+            if health.online && health.error_score <= backoff_threshold {
+                Some((*peer_id, health.error_score, health.ewma_latency_ms))
+            } else {
+                None
+            }
+        })
+        .min_by(|a, b| {
+            a.1.total_cmp(&b.1).then_with(|| a.2.total_cmp(&b.2))
+        })
+        .map(|(peer_id, _, _)| peer_id)
+}
+
+/// Spawns the reader loop for a connection: it dispatches each
+/// [`Protocol::Response`] to the pending oneshot matching its id and, when the
+/// stream ends or errors, drains every outstanding request with
+/// [`Response::None`] so no caller is left waiting.
+fn spawn_reader(
+    mut stream: SplitStream<Framed<TcpStream, RpcEncoder>>,
+    pending: Pending,
+    streams: PendingStreams,
+    health: Arc<Mutex<PeerHealth>>,
+    health_tx: Arc<watch::Sender<PeerHealth>>,
+    last_chunk: Arc<Mutex<Instant>>,
+    peer_addr: SocketAddr,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(frame) = stream.next().await {
+            match frame {
+                Ok(Protocol::Response { id, response }) => {
+                    if let Some((response_tx, sent_at)) = pending.lock().await.remove(&id) {
+                        // Fold the round-trip time into the peer's latency EWMA.
+                        let snapshot = {
+                            let mut health = health.lock().await;
+                            health.record_success(sent_at.elapsed());
+                            health.clone()
+                        };
+                        let _ = health_tx.send(snapshot);
+                        if response_tx.send(response).is_err() {
+                            debug!("Dropping response for abandoned request {}.", id);
+                        }
+                    }
+                }
+                Ok(Protocol::ResponseChunk { id, bytes }) => {
+                    // Poke the inactivity watchdog: an active transfer must not be
+                    // mistaken for an idle connection by the main loop.
+                    *last_chunk.lock().await = Instant::now();
+                    // Forward to the matching stream; a closed consumer just means
+                    // the remaining chunks for this id are discarded.
+                    let sender = streams.lock().await.get(&id).cloned();
+                    if let Some(sender) = sender {
+                        if sender.send(bytes).await.is_err() {
+                            streams.lock().await.remove(&id);
+                        }
+                    }
+                }
+                Ok(Protocol::ResponseEnd { id }) => {
+                    // Dropping the sender closes the consumer's stream cleanly.
+                    streams.lock().await.remove(&id);
+                }
+                Ok(invalid) => {
+                    error!("Received invalid RPC frame from {}: {:?}", peer_addr, invalid);
+                    break;
+                }
+                Err(err) => {
+                    debug!("RPC read error from {}: {}", peer_addr, err);
+                    break;
+                }
+            }
+        }
+        drain_pending(&pending, &streams).await;
+    })
+}
+
+/// Drops the write half, aborts the reader task and drains every pending request
+/// so in-flight callers observe the connection loss.
+async fn close_connection(
+    writer: &mut Option<SplitSink<Framed<TcpStream, RpcEncoder>, Protocol>>,
+    reader_handle: &mut Option<tokio::task::JoinHandle<()>>,
+    pending: &Pending,
+    streams: &PendingStreams,
+) {
+    *writer = None;
+    if let Some(handle) = reader_handle.take() {
+        handle.abort();
+    }
+    drain_pending(pending, streams).await;
+}
+
+/// Fulfills every outstanding unary request with [`Response::None`] and drops
+/// every streaming sender, so unary callers and stream consumers alike observe
+/// the connection loss rather than hanging.
+async fn drain_pending(pending: &Pending, streams: &PendingStreams) {
+    for (_, (response_tx, _)) in pending.lock().await.drain() {
+        let _ = response_tx.send(Response::None);
+    }
+    streams.lock().await.clear();
+}
+
+/// Sends `request` to every peer and returns as soon as `required` successful
+/// responses arrive, cancelling the rest. Per-peer failures and timeouts are
+/// skipped rather than aborting the call, so commit latency tracks the median
+/// peer instead of the slowest. Returns whatever responses were collected; the
+/// caller checks `len() >= required` to decide whether quorum was reached.
+pub async fn call_quorum(
+    peers: &[(PeerId, mpsc::Sender<RpcEvent>)],
+    request: Request,
+    required: usize,
+    timeout: Duration,
+) -> Vec<(PeerId, Response)> {
+    let mut inflight = FuturesUnordered::new();
+    for (peer_id, event_tx) in peers {
+        let (peer_id, event_tx, request) = (*peer_id, event_tx.clone(), request.clone());
+        inflight.push(async move {
+            let (response_tx, response_rx) = oneshot::channel();
+            if event_tx
+                .send(RpcEvent::NeedResponse {
+                    response_tx,
+                    request,
+                })
+                .await
+                .is_err()
+            {
+                return (peer_id, None);
+            }
+            (peer_id, response_rx.await.ok())
+        });
+    }
+
+    let mut responses = Vec::with_capacity(required);
+    let deadline = time::sleep(timeout);
+    tokio::pin!(deadline);
+
+    loop {
+        tokio::select! {
+            next = inflight.next() => match next {
+                Some((peer_id, Some(response))) if !matches!(response, Response::None) => {
+                    responses.push((peer_id, response));
+                    if responses.len() >= required {
+                        // Quorum reached: drop the remaining futures.
+                        break;
+                    }
+                }
+                // A failed/none response: record nothing and keep waiting.
+                Some(_) => {}
+                // All peers answered without reaching quorum.
+                None => break,
+            },
+            _ = &mut deadline => {
+                debug!("call_quorum timed out with {} of {} responses.", responses.len(), required);
+                break;
+            }
+        }
+    }
+
+    responses
 }
 
 async fn connect_peer(
@@ -247,9 +533,13 @@ async fn send_rpc(
     conn: &mut Framed<TcpStream, RpcEncoder>,
     request: Request,
 ) -> std::io::Result<Response> {
-    conn.send(Protocol::Request(request)).await?;
+    conn.send(Protocol::Request {
+        id: 0,
+        request,
+    })
+    .await?;
     match conn.next().await {
-        Some(Ok(Protocol::Response(response))) => Ok(response),
+        Some(Ok(Protocol::Response { response, .. })) => Ok(response),
         Some(Ok(invalid)) => Err(std::io::Error::new(
             std::io::ErrorKind::Other,
             format!("Received invalid RPC response: {:?}", invalid),
@@ -260,4 +550,4 @@ async fn send_rpc(
             "RPC connection unexpectedly closed.",
         )),
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,98 @@
+use std::io;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::{Request, Response};
+
+/// Maximum size of a single encoded frame, guarding against a corrupt length
+/// prefix allocating unbounded memory.
+const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+/// Wire envelope for the frames exchanged between cluster peers. Each variant
+/// is tagged with the `id` the writer assigned to the request it answers, so a
+/// single connection can multiplex multiple in-flight requests (see
+/// `peer::spawn_peer_rpc`'s `Pending` map).
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub enum Protocol {
+    /// A request tagged with the id its response will carry back.
+    Request { id: u32, request: Request },
+    /// The response to a unary request, tagged with the request's id.
+    Response { id: u32, response: Response },
+    /// One fragment of a streamed response, tagged with the request's id.
+    ResponseChunk { id: u32, bytes: Bytes },
+    /// Marks the end of a streamed response's chunk sequence.
+    ResponseEnd { id: u32 },
+}
+
+/// Length-prefixed `serde_json` codec for [`Protocol`] frames: a `u32`
+/// big-endian byte length followed by the JSON payload.
+#[derive(Debug, Default)]
+pub struct RpcEncoder {
+    /// Length of the frame currently being assembled, once its header has
+    /// been read.
+    frame_len: Option<usize>,
+}
+
+impl Encoder<Protocol> for RpcEncoder {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Protocol, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let payload = serde_json::to_vec(&item)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if payload.len() > MAX_FRAME_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "RPC frame of {} bytes exceeds the {} byte limit.",
+                    payload.len(),
+                    MAX_FRAME_SIZE
+                ),
+            ));
+        }
+        dst.reserve(4 + payload.len());
+        dst.put_u32(payload.len() as u32);
+        dst.put_slice(&payload);
+        Ok(())
+    }
+}
+
+impl Decoder for RpcEncoder {
+    type Item = Protocol;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let frame_len = match self.frame_len {
+            Some(len) => len,
+            None => {
+                if src.len() < 4 {
+                    return Ok(None);
+                }
+                let len = (&src[..4]).get_u32() as usize;
+                if len > MAX_FRAME_SIZE {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "RPC frame of {} bytes exceeds the {} byte limit.",
+                            len, MAX_FRAME_SIZE
+                        ),
+                    ));
+                }
+                src.advance(4);
+                self.frame_len = Some(len);
+                len
+            }
+        };
+
+        if src.len() < frame_len {
+            src.reserve(frame_len - src.len());
+            return Ok(None);
+        }
+
+        let frame = src.split_to(frame_len);
+        self.frame_len = None;
+        serde_json::from_slice(&frame)
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
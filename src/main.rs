@@ -21,8 +21,10 @@
  * for more details.
 */
 
+use std::sync::Arc;
 use std::time::Duration;
 
+use arc_swap::ArcSwap;
 use directory::config::ConfigDirectory;
 use jmap::{api::JmapSessionManager, services::IPC_CHANNEL_BUFFER, JMAP};
 use smtp::core::{SmtpSessionManager, SMTP};
@@ -32,6 +34,12 @@ use utils::{
     enable_tracing, wait_for_shutdown, UnwrapFailure,
 };
 
+mod acme;
+mod cluster;
+mod reload;
+use acme::{spawn_acme, AcmeConfig, AcmeManager};
+use reload::spawn_config_reload;
+
 #[cfg(not(target_env = "msvc"))]
 use jemallocator::Jemalloc;
 
@@ -45,6 +53,11 @@ async fn main() -> std::io::Result<()> {
     let servers = config.parse_servers().failed("Invalid configuration");
     let directory = config.parse_directory().failed("Invalid configuration");
 
+    // Keep the live configuration behind an ArcSwap handle that the SIGHUP
+    // reload task swaps atomically. Session managers are built from the
+    // boot-time configuration and do not read this handle.
+    let live_config = Arc::new(ArcSwap::from_pointee(config.clone()));
+
     // Bind ports and drop privileges
     servers.bind(&config);
 
@@ -92,6 +105,23 @@ async fn main() -> std::io::Result<()> {
         };
     });
 
+    // Listen for SIGHUP (and the internal admin channel) to hot-reload the
+    // configuration in place.
+    // Hold the admin sender for the life of the server: the reload task stops
+    // once every sender is dropped.
+    let _reload_tx = spawn_config_reload(live_config.clone(), directory.clone());
+
+    // Provision and renew TLS certificates via ACME when enabled. Renewed certs
+    // are installed through the live TLS handle so renewals happen with zero
+    // downtime.
+    if let Some(acme_config) = AcmeConfig::parse(&config) {
+        spawn_acme(std::sync::Arc::new(AcmeManager::new(
+            jmap.store.clone(),
+            acme_config,
+            servers.tls_config(),
+        )));
+    }
+
     // Wait for shutdown signal
     wait_for_shutdown(&format!(
         "Shutting down Stalwart JMAP Server v{}...",
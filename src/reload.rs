@@ -0,0 +1,107 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart JMAP Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use directory::config::ConfigDirectory;
+use directory::Directories;
+use tokio::sync::mpsc;
+use utils::config::Config;
+
+/// Settings that require a full restart; changing any of them on reload is
+/// logged and rejected rather than applied.
+const IMMUTABLE_KEYS: &[&str] = &["server.listener", "storage.data", "storage.blob"];
+
+/// Spawns the configuration reload task. Reloads are triggered by `SIGHUP` and
+/// by messages pushed on the returned internal admin channel; either re-parses
+/// the on-disk configuration, diffs it against the running state and applies the
+/// safely-mutable subset in place.
+pub fn spawn_config_reload(
+    live_config: Arc<ArcSwap<Config>>,
+    directory: Directories,
+) -> mpsc::Sender<()> {
+    let (admin_tx, mut admin_rx) = mpsc::channel::<()>(4);
+
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        let mut sighup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        {
+            Ok(signal) => signal,
+            Err(err) => {
+                tracing::error!("Failed to register SIGHUP handler: {}", err);
+                return;
+            }
+        };
+
+        loop {
+            #[cfg(unix)]
+            tokio::select! {
+                _ = sighup.recv() => {},
+                msg = admin_rx.recv() => {
+                    if msg.is_none() {
+                        break;
+                    }
+                }
+            };
+            #[cfg(not(unix))]
+            if admin_rx.recv().await.is_none() {
+                break;
+            }
+
+            match reload(&live_config, &directory) {
+                Ok(()) => tracing::info!("Configuration reloaded."),
+                Err(err) => tracing::error!("Configuration reload failed: {}", err),
+            }
+        }
+    });
+
+    admin_tx
+}
+
+fn reload(live_config: &ArcSwap<Config>, directory: &Directories) -> Result<(), String> {
+    let new_config = Config::init();
+    let current = live_config.load();
+
+    // Reject changes to settings that cannot be applied without a restart.
+    for key in IMMUTABLE_KEYS {
+        if current.value(key) != new_config.value(key) {
+            return Err(format!(
+                "'{}' cannot be changed at runtime, a restart is required.",
+                key
+            ));
+        }
+    }
+
+    // Apply the mutable subset in place.
+    let _ = utils::enable_tracing(&new_config, "Reloading tracing subscriber...");
+    if let Ok(backends) = new_config.parse_directory() {
+        directory.replace(backends);
+    }
+
+    // Swap in the new snapshot for readers of the live handle; running session
+    // managers keep the configuration they were built with.
+    live_config.store(Arc::new(new_config));
+
+    Ok(())
+}